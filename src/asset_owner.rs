@@ -1,4 +1,19 @@
-use {bevy::prelude::*, std::marker::PhantomData};
+use {
+    bevy::{
+        asset::{LoadState, UntypedAssetId},
+        prelude::*,
+    },
+    std::marker::PhantomData,
+};
+
+/// Whether the asset is done loading, successfully or not, so that nothing waits forever on
+/// a missing file.
+pub fn has_finished_loading(asset_server: &AssetServer, id: impl Into<UntypedAssetId>) -> bool {
+    matches!(
+        asset_server.load_state(id),
+        LoadState::Loaded | LoadState::Failed(_)
+    )
+}
 
 #[derive(Resource)]
 pub struct TextureAtlasOwner<T> {
@@ -23,6 +38,10 @@ impl<T: Component> TextureAtlasOwner<T> {
     pub fn layout(&self) -> Handle<TextureAtlasLayout> {
         self.layout.clone_weak()
     }
+
+    pub fn has_finished_loading(&self, asset_server: &AssetServer) -> bool {
+        has_finished_loading(asset_server, &self.tex)
+    }
 }
 
 #[derive(Resource)]
@@ -0,0 +1,451 @@
+use {
+    super::{
+        animation::{AnimationIndices, AnimationState, AnimationTimer},
+        asset_owner::{FontOwner, TextureAtlasOwner},
+        combat::Health,
+        controls::{key_label, MenuInput},
+        level::{LevelInfo, RunSeed},
+        movement::{MovementProfile, MovementProfileOwner},
+        player::{Player, PlayerAnimation, PlayerCount, MAX_PLAYERS, PLAYER_SPRITE_SIZE},
+        run_state::{RunModifier, RunState},
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    serde::{Deserialize, Serialize},
+    static_assertions::const_assert,
+    std::time::Duration,
+};
+
+const PREV_KEY: KeyCode = KeyCode::ArrowLeft;
+const PREV_BUTTON: GamepadButtonType = GamepadButtonType::DPadLeft;
+const NEXT_KEY: KeyCode = KeyCode::ArrowRight;
+const NEXT_BUTTON: GamepadButtonType = GamepadButtonType::DPadRight;
+const PLAYER_COUNT_KEY: KeyCode = KeyCode::Tab;
+const PLAYER_COUNT_BUTTON: GamepadButtonType = GamepadButtonType::Select;
+const CONFIRM_KEY: KeyCode = KeyCode::Enter;
+const CONFIRM_BUTTON: GamepadButtonType = GamepadButtonType::South;
+const CONTINUE_KEY: KeyCode = KeyCode::KeyC;
+const CONTINUE_BUTTON: GamepadButtonType = GamepadButtonType::North;
+/// Toggles for each entry of [`RunModifier::ALL`].
+const MODIFIER_TOGGLES: [(KeyCode, GamepadButtonType); RunModifier::ALL.len()] = [
+    (KeyCode::Digit1, GamepadButtonType::LeftTrigger),
+    (KeyCode::Digit2, GamepadButtonType::RightTrigger),
+];
+const CARD_COLOR: Color = Color::srgb(0.15, 0.15, 0.15);
+const SELECTED_CARD_COLOR: Color = Color::srgb(0.4, 0.4, 0.4);
+
+/// A playable character, each with its own sprite sheet and stats.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Default, Debug, Serialize, Deserialize)]
+pub enum Character {
+    #[default]
+    Player,
+    Adventurer,
+    Female,
+    Soldier,
+    Zombie,
+}
+
+/// Everything about a character but its movement, which is tuned in the movement profile.
+#[derive(Clone, Copy)]
+pub struct CharacterStats {
+    pub max_health: Health,
+}
+
+/// Indexed like [`Character::ALL`].
+const CHARACTER_STATS: [CharacterStats; Character::ALL.len()] = [
+    CharacterStats {
+        max_health: Health(10),
+    },
+    CharacterStats {
+        max_health: Health(8),
+    },
+    CharacterStats {
+        max_health: Health(8),
+    },
+    CharacterStats {
+        max_health: Health(14),
+    },
+    CharacterStats {
+        max_health: Health(16),
+    },
+];
+
+const_assert!({
+    let mut i = 0;
+    let mut are_hearts_whole = true;
+    while i < CHARACTER_STATS.len() {
+        let max_health = CHARACTER_STATS[i].max_health.0;
+        are_hearts_whole &= max_health > 0 && max_health % 2 == 0;
+        i += 1;
+    }
+    are_hearts_whole
+});
+
+impl Character {
+    pub const ALL: [Self; 5] = [
+        Self::Player,
+        Self::Adventurer,
+        Self::Female,
+        Self::Soldier,
+        Self::Zombie,
+    ];
+
+    fn sprite_sheet(self) -> &'static str {
+        match self {
+            Character::Player => "player.png",
+            Character::Adventurer => "adventurer.png",
+            Character::Female => "female.png",
+            Character::Soldier => "soldier.png",
+            Character::Zombie => "zombie.png",
+        }
+    }
+
+    pub fn stats(self) -> CharacterStats {
+        CHARACTER_STATS[self as usize]
+    }
+
+    /// Where `animation` lives on this character's sprite sheet and how fast it plays.
+    /// The sheets share a layout, so only deviations from it are listed here.
+    pub fn animation(self, animation: PlayerAnimation) -> (AnimationIndices, AnimationTimer) {
+        match (self, animation) {
+            (Character::Zombie, PlayerAnimation::Running) => (
+                animation.indices(),
+                AnimationTimer::new(Duration::from_secs_f32(2f32.recip())),
+            ),
+            (Character::Zombie, PlayerAnimation::Attacking) => {
+                (AnimationIndices::new(11, 11), animation.timer())
+            }
+            _ => (animation.indices(), animation.timer()),
+        }
+    }
+}
+
+/// The character picked for the current run, played by every player. Co-op shares one pick
+/// on purpose, so that the select screen stays usable from a single keyboard.
+#[derive(Resource, Default)]
+pub struct SelectedCharacter(pub Character);
+
+/// The modifiers picked for the next run.
+#[derive(Resource, Default)]
+pub struct SelectedModifiers(pub Vec<RunModifier>);
+
+/// One texture atlas per entry of [`Character::ALL`].
+#[derive(Resource)]
+pub struct CharacterAtlases([TextureAtlasOwner<Player>; Character::ALL.len()]);
+
+impl CharacterAtlases {
+    pub fn get(&self, character: Character) -> &TextureAtlasOwner<Player> {
+        &self.0[character as usize]
+    }
+
+    pub fn have_finished_loading(&self, asset_server: &AssetServer) -> bool {
+        self.0
+            .iter()
+            .all(|atlas| atlas.has_finished_loading(asset_server))
+    }
+}
+
+#[derive(Component)]
+struct CharacterSelectScreen;
+
+#[derive(Component)]
+struct CharacterCard(Character);
+
+#[derive(Component)]
+struct CharacterStatsText(Character);
+
+#[derive(Component)]
+struct PlayerCountText;
+
+#[derive(Component)]
+struct ModifiersText;
+
+/// The run saved between levels, offered on the character select screen when there is one.
+#[derive(Resource)]
+struct SavedRun(RunState);
+
+fn spawn_character_select_screen(
+    mut cmds: Commands,
+    screen_font: Res<FontOwner<CharacterSelectScreen>>,
+    character_atlases: Res<CharacterAtlases>,
+) {
+    let text_style = |font_size| TextStyle {
+        font: screen_font.font(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    let saved_run = RunState::load();
+    let continue_hint = saved_run
+        .as_ref()
+        .map(|saved_run| {
+            format!(
+                "    [{continue_key}/{CONTINUE_BUTTON:?}] Continue {}",
+                saved_run.level,
+                continue_key = key_label(CONTINUE_KEY),
+            )
+        })
+        .unwrap_or_default();
+    match saved_run {
+        Some(saved_run) => cmds.insert_resource(SavedRun(saved_run)),
+        None => cmds.remove_resource::<SavedRun>(),
+    }
+
+    cmds.spawn((
+        CharacterSelectScreen,
+        StateScoped(GameState::CharacterSelect),
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(40.),
+                ..default()
+            },
+            background_color: Color::BLACK.into(),
+            ..default()
+        },
+    ))
+    .with_children(|screen| {
+        screen.spawn(TextBundle::from_section(
+            "Choose your character",
+            text_style(60.),
+        ));
+        screen
+            .spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(20.),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|cards| {
+                for character in Character::ALL {
+                    cards
+                        .spawn((
+                            CharacterCard(character),
+                            NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    align_items: AlignItems::Center,
+                                    padding: UiRect::all(Val::Px(10.)),
+                                    row_gap: Val::Px(8.),
+                                    ..default()
+                                },
+                                background_color: CARD_COLOR.into(),
+                                ..default()
+                            },
+                        ))
+                        .with_children(|card| {
+                            let character_assets = character_atlases.get(character);
+                            card.spawn((
+                                ImageBundle {
+                                    image: UiImage::new(character_assets.texture()),
+                                    style: Style {
+                                        width: Val::Px(PLAYER_SPRITE_SIZE.x as f32),
+                                        height: Val::Px(PLAYER_SPRITE_SIZE.y as f32),
+                                        ..default()
+                                    },
+                                    ..default()
+                                },
+                                TextureAtlas {
+                                    layout: character_assets.layout(),
+                                    index: 0,
+                                },
+                            ));
+                            card.spawn(TextBundle::from_section(
+                                format!("{character:?}"),
+                                text_style(32.),
+                            ));
+                            card.spawn((
+                                CharacterStatsText(character),
+                                TextBundle::from_section(String::new(), text_style(24.)),
+                            ));
+                        });
+                }
+            });
+        screen.spawn((
+            PlayerCountText,
+            TextBundle::from_section(String::new(), text_style(32.)),
+        ));
+        screen.spawn((
+            ModifiersText,
+            TextBundle::from_section(String::new(), text_style(32.)),
+        ));
+        screen.spawn(TextBundle::from_section(
+            format!(
+                "[{prev_key}/{next_key}] Choose    \
+                 [{player_count_key}/{PLAYER_COUNT_BUTTON:?}] Players    \
+                 [{confirm_key}/{CONFIRM_BUTTON:?}] Start{continue_hint}\n{modifier_hint}",
+                prev_key = key_label(PREV_KEY),
+                next_key = key_label(NEXT_KEY),
+                player_count_key = key_label(PLAYER_COUNT_KEY),
+                confirm_key = key_label(CONFIRM_KEY),
+                modifier_hint = RunModifier::ALL
+                    .iter()
+                    .zip(MODIFIER_TOGGLES)
+                    .map(|(modifier, (key, button))| {
+                        format!("[{}/{button:?}] {modifier:?}", key_label(key))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("    "),
+            ),
+            text_style(32.),
+        ));
+    });
+}
+
+fn handle_character_select_input(
+    menu_in: MenuInput,
+    mut selected_character: ResMut<SelectedCharacter>,
+    mut player_count: ResMut<PlayerCount>,
+    mut selected_modifiers: ResMut<SelectedModifiers>,
+    saved_run: Option<Res<SavedRun>>,
+    mut run_seed: ResMut<RunSeed>,
+    mut level_info: ResMut<LevelInfo>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut cmds: Commands,
+) {
+    let selected_idx = selected_character.0 as usize;
+    if menu_in.just_pressed(PREV_KEY, PREV_BUTTON) {
+        selected_character.0 =
+            Character::ALL[(selected_idx + Character::ALL.len() - 1) % Character::ALL.len()];
+    } else if menu_in.just_pressed(NEXT_KEY, NEXT_BUTTON) {
+        selected_character.0 = Character::ALL[(selected_idx + 1) % Character::ALL.len()];
+    } else if menu_in.just_pressed(PLAYER_COUNT_KEY, PLAYER_COUNT_BUTTON) {
+        player_count.0 = player_count.0 % MAX_PLAYERS + 1;
+    } else if let Some(modifier) = RunModifier::ALL
+        .into_iter()
+        .zip(MODIFIER_TOGGLES)
+        .find(|&(_, (key, button))| menu_in.just_pressed(key, button))
+        .map(|(modifier, _)| modifier)
+    {
+        let modifiers = &mut selected_modifiers.0;
+        match modifiers.iter().position(|&selected| selected == modifier) {
+            Some(idx) => {
+                modifiers.remove(idx);
+            }
+            None => modifiers.push(modifier),
+        }
+    } else if menu_in.just_pressed(CONFIRM_KEY, CONFIRM_BUTTON) {
+        next_state.set(GameState::Playing);
+    } else if let Some(saved_run) = saved_run
+        .filter(|_| menu_in.just_pressed(CONTINUE_KEY, CONTINUE_BUTTON))
+        .map(|saved_run| saved_run.0.clone())
+    {
+        *run_seed = RunSeed(saved_run.seed);
+        *level_info = saved_run.level.clone();
+        player_count.0 = saved_run.players.len();
+        if let Some(first_player) = saved_run.players.first() {
+            selected_character.0 = first_player.character;
+        }
+        cmds.insert_resource(saved_run);
+        next_state.set(GameState::Playing);
+    }
+}
+
+/// Kept in sync with the movement profile, which may still be loading or be edited live.
+fn update_character_stats_texts(
+    mut stats_text_qry: Query<(&CharacterStatsText, &mut Text)>,
+    profile_owner: Res<MovementProfileOwner>,
+    profiles: Res<Assets<MovementProfile>>,
+) {
+    for (stats_text, mut text) in &mut stats_text_qry {
+        let character = stats_text.0;
+        let tuning = profile_owner.tuning(&profiles, character, None);
+        text.sections[0].value = format!(
+            "HP {}\nSpeed {:.1}\nJump {:.2}",
+            character.stats().max_health.0,
+            tuning.run_speed,
+            tuning.jump_height,
+        );
+    }
+}
+
+fn update_player_count_text(
+    player_count: Res<PlayerCount>,
+    mut player_count_text_qry: Query<&mut Text, With<PlayerCountText>>,
+) {
+    for mut player_count_text in &mut player_count_text_qry {
+        player_count_text.sections[0].value = match player_count.0 {
+            1 => String::from("1 player"),
+            count => format!("{count} players"),
+        };
+    }
+}
+
+fn update_modifiers_text(
+    selected_modifiers: Res<SelectedModifiers>,
+    mut modifiers_text_qry: Query<&mut Text, With<ModifiersText>>,
+) {
+    for mut modifiers_text in &mut modifiers_text_qry {
+        modifiers_text.sections[0].value = if selected_modifiers.0.is_empty() {
+            String::from("No modifiers")
+        } else {
+            selected_modifiers
+                .0
+                .iter()
+                .map(|modifier| format!("{modifier:?}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+    }
+}
+
+fn highlight_selected_card(
+    selected_character: Res<SelectedCharacter>,
+    mut card_qry: Query<(&CharacterCard, &mut BackgroundColor)>,
+) {
+    for (card, mut card_color) in &mut card_qry {
+        card_color.0 = if card.0 == selected_character.0 {
+            SELECTED_CARD_COLOR
+        } else {
+            CARD_COLOR
+        };
+    }
+}
+
+pub fn character_plugin(app: &mut App) {
+    app.init_resource::<SelectedCharacter>()
+        .init_resource::<SelectedModifiers>()
+        .add_systems(
+            OnEnter(GameState::Setup),
+            |mut cmds: Commands,
+             asset_server: Res<AssetServer>,
+             mut tex_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>| {
+                cmds.insert_resource(CharacterAtlases(Character::ALL.map(|character| {
+                    TextureAtlasOwner::new(
+                        asset_server.load(character.sprite_sheet()),
+                        tex_atlas_layouts.add(TextureAtlasLayout::from_grid(
+                            PLAYER_SPRITE_SIZE,
+                            9,
+                            3,
+                            None,
+                            None,
+                        )),
+                    )
+                })));
+                cmds.insert_resource(FontOwner::<CharacterSelectScreen>::new(
+                    asset_server.load("font.ttf"),
+                ));
+            },
+        )
+        .add_systems(
+            OnEnter(GameState::CharacterSelect),
+            spawn_character_select_screen,
+        )
+        .add_systems(
+            Update,
+            (
+                handle_character_select_input,
+                highlight_selected_card,
+                update_character_stats_texts,
+                update_player_count_text,
+                update_modifiers_text,
+            )
+                .chain()
+                .run_if(in_state(GameState::CharacterSelect)),
+        );
+}
@@ -1,21 +1,110 @@
 use {
-    super::GameState,
+    super::{player::DoorTransit, tile::TILE_SIZE, GameState},
     bevy::prelude::*,
     bevy_rapier2d::prelude::*,
+    serde::{Deserialize, Serialize},
     std::{f32::consts::TAU, time::Duration},
 };
 
-#[derive(Component, Clone, Copy)]
+/// Added to the knockback direction so that hits pop targets off the ground.
+const KNOCKBACK_LIFT: f32 = 0.5;
+const HIT_STUN_DURATION: Duration = Duration::from_millis(250);
+
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
 pub struct Health(pub i8);
 
-#[derive(Component)]
-pub enum Damage {
+impl Health {
+    /// Returns whether this was the damage that brought the health down to 0.
+    pub fn take(&mut self, amount: i8) -> bool {
+        let was_alive = self.0 > 0;
+        self.0 -= amount;
+        was_alive && self.0 <= 0
+    }
+}
+
+/// The [`Health`] shown as hearts, raised by heart containers.
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct MaxHealth(pub i8);
+
+#[derive(Clone, Copy)]
+pub enum DamageAmount {
     Kill,
     Fixed(i8),
 }
 
+#[derive(Component, Clone, Copy)]
+pub struct Damage {
+    pub amount: DamageAmount,
+    /// How fast targets are knocked away from the source, in tiles per second. Only targets
+    /// with a [`Velocity`], such as players, are knocked back; tiles are just damaged.
+    pub knockback: f32,
+}
+
+impl Damage {
+    pub const fn fixed(amount: i8, knockback: f32) -> Self {
+        Self {
+            amount: DamageAmount::Fixed(amount),
+            knockback,
+        }
+    }
+}
+
+/// What last dealt damage to an entity, taken from the damage source's [`Name`].
+#[derive(Component, Clone)]
+pub struct LastHitBy(pub String);
+
+/// Who a damage source belongs to when it is not attached to them, e.g. a thrown projectile.
+#[derive(Component)]
+pub struct DamageOwner(pub Entity);
+
+/// Marks level hazards such as spikes, whose damage can be resisted.
 #[derive(Component)]
-struct Iframes {
+pub struct Hazard;
+
+/// How much damage is shaved off every hit taken from a [`Hazard`].
+#[derive(Component, Clone, Copy)]
+pub struct HazardResistance(pub i8);
+
+/// Entities with this are not despawned on [`DeathEvent`] so they can play out their own death.
+#[derive(Component)]
+pub struct KeepOnDeath;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DamageKind {
+    Hit,
+    Hazard,
+    Fall,
+}
+
+/// Sent whenever an entity loses health.
+#[derive(Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    /// Who dealt the damage: the damage source's [`DamageOwner`], else its parent, else the
+    /// source itself. `None` for damage with no source entity, such as a fall.
+    pub source: Option<Entity>,
+    /// The damage source's [`Name`], for display only.
+    pub source_name: Option<String>,
+    pub amount: i8,
+    pub kind: DamageKind,
+}
+
+/// Sent when damage brings an entity's health down to 0.
+#[derive(Event)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    /// The killing blow's source, as in [`DamageEvent`].
+    pub source: Option<Entity>,
+    pub source_name: Option<String>,
+}
+
+/// Present while a knocked back entity recovers from a hit, so never on one without a
+/// [`Velocity`].
+#[derive(Component)]
+pub struct HitStun(Timer);
+
+#[derive(Component)]
+pub struct Iframes {
     timer: Timer,
 }
 
@@ -28,65 +117,181 @@ impl Iframes {
             timer: Timer::new(duration, TimerMode::Once),
         }
     }
+
+    pub fn remaining(&self) -> Duration {
+        self.timer.remaining()
+    }
 }
 
-fn deal_damage(
-    mut hp_qry: Query<(Entity, &mut Health, Has<Sensor>), (With<Collider>, Without<Iframes>)>,
-    dmg_qry: Query<(Entity, &Damage, Has<Sensor>), With<Collider>>,
+pub fn deal_damage(
+    mut hp_qry: Query<
+        (
+            Entity,
+            &mut Health,
+            Has<Sensor>,
+            Option<&HazardResistance>,
+            &GlobalTransform,
+            Option<&mut Velocity>,
+        ),
+        (With<Collider>, Without<Iframes>),
+    >,
+    dmg_qry: Query<
+        (
+            Entity,
+            &Damage,
+            Has<Sensor>,
+            Option<&Name>,
+            Option<&Parent>,
+            Option<&DamageOwner>,
+            Has<Hazard>,
+            &GlobalTransform,
+        ),
+        With<Collider>,
+    >,
     rapier_ctx: Res<RapierContext>,
+    mut dmg_evw: EventWriter<DamageEvent>,
+    mut death_evw: EventWriter<DeathEvent>,
     mut cmds: Commands,
 ) {
-    for (hp_id, mut hp, hp_has_sensor) in &mut hp_qry {
-        for (dmg_id, dmg, dmg_has_sensor) in &dmg_qry {
-            if (hp_id != dmg_id)
+    for (hp_id, mut hp, hp_has_sensor, hp_hazard_resistance, hp_xform, mut hp_velocity) in
+        &mut hp_qry
+    {
+        for (
+            dmg_id,
+            dmg,
+            dmg_has_sensor,
+            dmg_name,
+            dmg_parent,
+            dmg_owner,
+            dmg_is_hazard,
+            dmg_xform,
+        ) in &dmg_qry
+        {
+            // Damage never hurts whatever is wielding or threw it, nor what is already dead.
+            if (hp.0 > 0)
+                && (hp_id != dmg_id)
+                && (dmg_parent.map(Parent::get) != Some(hp_id))
+                && (dmg_owner.map(|owner| owner.0) != Some(hp_id))
                 && (hp_has_sensor || dmg_has_sensor)
                 && (rapier_ctx.intersection_pair(hp_id, dmg_id) == Some(true))
             {
-                match dmg {
-                    Damage::Kill => hp.0 = 0,
-                    &Damage::Fixed(dmg) => {
-                        hp.0 -= dmg;
+                if let Some(dmg_name) = dmg_name {
+                    cmds.entity(hp_id).insert(LastHitBy(dmg_name.to_string()));
+                }
+                let amount = match dmg.amount {
+                    DamageAmount::Kill => hp.0,
+                    DamageAmount::Fixed(mut dmg) => {
+                        if let (true, Some(resistance)) = (dmg_is_hazard, hp_hazard_resistance) {
+                            dmg = (dmg - resistance.0).max(0);
+                        }
                         if dmg > 0 {
                             cmds.entity(hp_id)
                                 .insert(Iframes::new(Duration::from_secs_f32(
                                     Iframes::SECONDS_PER_DAMAGE * dmg as f32,
                                 )));
                         }
+                        dmg
+                    }
+                };
+                if amount > 0 {
+                    let source = dmg_owner
+                        .map(|owner| owner.0)
+                        .or(dmg_parent.map(Parent::get))
+                        .unwrap_or(dmg_id);
+                    let source_name = dmg_name.map(Name::to_string);
+                    if hp.take(amount) {
+                        death_evw.send(DeathEvent {
+                            entity: hp_id,
+                            source: Some(source),
+                            source_name: source_name.clone(),
+                        });
+                    }
+                    dmg_evw.send(DamageEvent {
+                        target: hp_id,
+                        source: Some(source),
+                        source_name,
+                        amount,
+                        kind: if dmg_is_hazard {
+                            DamageKind::Hazard
+                        } else {
+                            DamageKind::Hit
+                        },
+                    });
+                    if let (Some(hp_velocity), true) = (&mut hp_velocity, dmg.knockback > 0.) {
+                        let away = (hp_xform.translation() - dmg_xform.translation())
+                            .truncate()
+                            .normalize_or_zero();
+                        hp_velocity.linvel = (away + KNOCKBACK_LIFT * Vec2::Y).normalize()
+                            * dmg.knockback
+                            * TILE_SIZE.x;
+                        cmds.entity(hp_id)
+                            .insert(HitStun(Timer::new(HIT_STUN_DURATION, TimerMode::Once)));
                     }
                 }
             }
         }
-        if hp.0 <= 0 {
-            cmds.entity(hp_id).despawn_recursive();
+    }
+}
+
+fn despawn_dead(
+    mut death_evr: EventReader<DeathEvent>,
+    keep_qry: Query<(), With<KeepOnDeath>>,
+    mut cmds: Commands,
+) {
+    for death in death_evr.read() {
+        if keep_qry.contains(death.entity) {
+            continue;
+        }
+        if let Some(dead) = cmds.get_entity(death.entity) {
+            dead.despawn_recursive();
+        }
+    }
+}
+
+fn update_hit_stuns(
+    time: Res<Time>,
+    mut stun_qry: Query<(Entity, &mut HitStun)>,
+    mut cmds: Commands,
+) {
+    for (id, mut stun) in &mut stun_qry {
+        if stun.0.tick(time.delta()).finished() {
+            cmds.entity(id).remove::<HitStun>();
         }
     }
 }
 
 fn update_iframes(
     time: Res<Time>,
-    mut iframes_qry: Query<(Entity, &mut Iframes, &mut Sprite)>,
+    mut iframes_qry: Query<(Entity, &mut Iframes, &mut Sprite, Has<DoorTransit>)>,
     mut cmds: Commands,
 ) {
     let dt = time.delta();
 
-    for (id, mut invincible, mut sprite) in &mut iframes_qry {
+    for (id, mut invincible, mut sprite, is_in_transit) in &mut iframes_qry {
         invincible.timer.tick(dt);
-        sprite.color.set_alpha(f32::sin(
-            invincible.timer.elapsed_secs() * Iframes::FREQUENCY * TAU,
-        ));
-
         if invincible.timer.just_finished() {
             cmds.entity(id).remove::<Iframes>();
-            sprite.color.set_alpha(1.);
         }
+        // Going through a door fades the sprite instead.
+        if is_in_transit {
+            continue;
+        }
+        sprite.color.set_alpha(if invincible.timer.finished() {
+            1.
+        } else {
+            f32::sin(invincible.timer.elapsed_secs() * Iframes::FREQUENCY * TAU)
+        });
     }
 }
 
 pub fn combat_plugin(app: &mut App) {
-    app.add_systems(
-        Update,
-        (deal_damage, update_iframes)
-            .chain()
-            .run_if(in_state(GameState::Playing)),
-    );
+    app.add_event::<DamageEvent>()
+        .add_event::<DeathEvent>()
+        .add_systems(
+            FixedUpdate,
+            (deal_damage, despawn_dead, update_hit_stuns, update_iframes)
+                .chain()
+                .after(PhysicsSet::Writeback)
+                .run_if(in_state(GameState::Playing)),
+        );
 }
@@ -0,0 +1,607 @@
+use {
+    super::{asset_owner::FontOwner, player::PlayerAction},
+    crate::GameState,
+    bevy::{ecs::system::SystemParam, input::InputSystem, prelude::*},
+    leafwing_input_manager::{plugin::InputManagerSystem, prelude::*},
+    serde::{Deserialize, Serialize},
+    std::fs,
+};
+
+const CONTROLS_PATH: &str = "controls.ron";
+const CONTROLS_MENU_KEY: KeyCode = KeyCode::F1;
+const CONTROLS_MENU_BUTTON: GamepadButtonType = GamepadButtonType::Start;
+const GAMEPAD_DEADZONE: f32 = 0.25;
+const CONTROLS_MENU_Z_INDEX: ZIndex = ZIndex::Global(2);
+const DEFAULT_BINDINGS: [(PlayerAction, KeyCode); 8] = [
+    (PlayerAction::MoveLeft, KeyCode::KeyA),
+    (PlayerAction::MoveRight, KeyCode::KeyD),
+    (PlayerAction::Jump, KeyCode::KeyW),
+    (PlayerAction::DropDown, KeyCode::KeyS),
+    (PlayerAction::EnterDoor, KeyCode::Space),
+    (PlayerAction::Dash, KeyCode::ShiftLeft),
+    (PlayerAction::Attack, KeyCode::KeyJ),
+    (PlayerAction::Throw, KeyCode::KeyK),
+];
+const MOUSE_BUTTON_BINDINGS: [(PlayerAction, MouseButton); 1] =
+    [(PlayerAction::Throw, MouseButton::Left)];
+const GAMEPAD_BUTTON_BINDINGS: [(PlayerAction, GamepadButtonType); 8] = [
+    (PlayerAction::MoveLeft, GamepadButtonType::DPadLeft),
+    (PlayerAction::MoveRight, GamepadButtonType::DPadRight),
+    (PlayerAction::Jump, GamepadButtonType::South),
+    (PlayerAction::DropDown, GamepadButtonType::DPadDown),
+    (PlayerAction::EnterDoor, GamepadButtonType::West),
+    (PlayerAction::Dash, GamepadButtonType::RightTrigger),
+    (PlayerAction::Attack, GamepadButtonType::North),
+    (PlayerAction::Throw, GamepadButtonType::RightTrigger2),
+];
+const GAMEPAD_STICK_BINDINGS: [(PlayerAction, GamepadControlDirection); 3] = [
+    (PlayerAction::MoveLeft, GamepadControlDirection::LEFT_LEFT),
+    (PlayerAction::MoveRight, GamepadControlDirection::LEFT_RIGHT),
+    (PlayerAction::DropDown, GamepadControlDirection::LEFT_DOWN),
+];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct Binding {
+    action: PlayerAction,
+    key: KeyCode,
+}
+
+/// Keyboard bindings for every [`PlayerAction`], persisted to [`CONTROLS_PATH`].
+/// Mouse and gamepad bindings are fixed and always added on top.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Controls {
+    bindings: Vec<Binding>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULT_BINDINGS
+                .map(|(action, key)| Binding { action, key })
+                .to_vec(),
+        }
+    }
+}
+
+impl Controls {
+    /// Reads the config file, falling back to the defaults for anything missing or invalid.
+    fn load() -> Self {
+        let mut controls = match fs::read_to_string(CONTROLS_PATH) {
+            Ok(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+                warn!("ignoring invalid {CONTROLS_PATH}: {err}");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+
+        for (action, key) in DEFAULT_BINDINGS {
+            if !controls
+                .bindings
+                .iter()
+                .any(|binding| binding.action == action)
+            {
+                controls.bindings.push(Binding { action, key });
+            }
+        }
+        controls
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(CONTROLS_PATH, contents).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            error!("failed to save {CONTROLS_PATH}: {err}");
+        }
+    }
+
+    pub fn key(&self, action: &PlayerAction) -> Option<KeyCode> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.action == *action)
+            .map(|binding| binding.key)
+    }
+
+    /// How to show the binding of `action` to a player using `devices`, e.g. `Space/West`.
+    pub fn action_label(&self, action: &PlayerAction, devices: InputDevices) -> String {
+        let key = self
+            .key(action)
+            .filter(|_| devices.has_mouse())
+            .map(key_label);
+        let button = GAMEPAD_BUTTON_BINDINGS
+            .iter()
+            .find(|(button_action, _)| button_action == action)
+            .filter(|_| devices != InputDevices::KeyboardAndMouse)
+            .map(|(_, button)| format!("{button:?}"));
+        key.into_iter().chain(button).collect::<Vec<_>>().join("/")
+    }
+
+    /// Binds `key` to `action`, or returns the action that already uses `key`.
+    fn rebind(&mut self, action: &PlayerAction, key: KeyCode) -> Result<(), PlayerAction> {
+        if let Some(conflict) = self
+            .bindings
+            .iter()
+            .find(|binding| binding.key == key && binding.action != *action)
+        {
+            return Err(conflict.action.clone());
+        }
+        if let Some(binding) = self
+            .bindings
+            .iter_mut()
+            .find(|binding| binding.action == *action)
+        {
+            binding.key = key;
+        }
+        Ok(())
+    }
+
+    pub fn input_map(&self, devices: InputDevices) -> InputMap<PlayerAction> {
+        let mut input_map = InputMap::default();
+        if devices.has_mouse() {
+            for binding in &self.bindings {
+                input_map.insert(binding.action.clone(), binding.key);
+            }
+            for (action, button) in MOUSE_BUTTON_BINDINGS {
+                input_map.insert(action, button);
+            }
+        }
+        if devices != InputDevices::KeyboardAndMouse {
+            for (action, button) in GAMEPAD_BUTTON_BINDINGS {
+                input_map.insert(action, button);
+            }
+            for (action, direction) in GAMEPAD_STICK_BINDINGS {
+                input_map.insert(action, direction);
+            }
+        }
+        if let InputDevices::Gamepad(gamepad) = devices {
+            input_map.set_gamepad(gamepad);
+        }
+        input_map
+    }
+}
+
+/// Which devices drive a player.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum InputDevices {
+    /// Keyboard, mouse and any gamepad, for playing alone.
+    All,
+    KeyboardAndMouse,
+    Gamepad(Gamepad),
+}
+
+impl InputDevices {
+    /// The first player keeps the keyboard and mouse, the others get a gamepad each,
+    /// in the order they were connected.
+    pub fn for_player(idx: usize, player_count: usize, gamepads: &Gamepads) -> Self {
+        match idx {
+            _ if player_count <= 1 => Self::All,
+            0 => Self::KeyboardAndMouse,
+            _ => Self::Gamepad(
+                gamepads
+                    .iter()
+                    .nth(idx - 1)
+                    .unwrap_or(Gamepad::new(idx - 1)),
+            ),
+        }
+    }
+
+    pub fn has_mouse(self) -> bool {
+        !matches!(self, Self::Gamepad(_))
+    }
+}
+
+/// Menu input that accepts either a keyboard key or a button on any gamepad.
+#[derive(SystemParam)]
+pub struct MenuInput<'w> {
+    kb: Res<'w, ButtonInput<KeyCode>>,
+    pad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+}
+
+impl MenuInput<'_> {
+    pub fn just_pressed(&self, key: KeyCode, button: GamepadButtonType) -> bool {
+        self.kb.just_pressed(key)
+            || self
+                .pad_buttons
+                .get_just_pressed()
+                .any(|pad_button| pad_button.button_type == button)
+    }
+
+    fn just_pressed_key(&self) -> Option<KeyCode> {
+        self.kb.get_just_pressed().next().copied()
+    }
+}
+
+/// A short, human readable name for `key`, e.g. `A` rather than `KeyA`.
+pub fn key_label(key: KeyCode) -> String {
+    let label = format!("{key:?}");
+    label
+        .strip_prefix("Key")
+        .or_else(|| label.strip_prefix("Digit"))
+        .unwrap_or(&label)
+        .to_owned()
+}
+
+#[derive(Component)]
+struct ControlsMenu {
+    selected: usize,
+    is_rebinding: bool,
+    status: String,
+}
+
+#[derive(Component)]
+struct ControlsMenuRow(usize);
+
+#[derive(Component)]
+struct ControlsMenuStatus;
+
+pub fn controls_menu_closed(menu_qry: Query<(), With<ControlsMenu>>) -> bool {
+    menu_qry.is_empty()
+}
+
+fn toggle_controls_menu(
+    menu_in: MenuInput,
+    menu_qry: Query<(Entity, &ControlsMenu)>,
+    controls: Res<Controls>,
+    menu_font: Res<FontOwner<ControlsMenu>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut cmds: Commands,
+) {
+    if !menu_in.just_pressed(CONTROLS_MENU_KEY, CONTROLS_MENU_BUTTON) {
+        return;
+    }
+    if let Ok((menu_id, menu)) = menu_qry.get_single() {
+        if !menu.is_rebinding {
+            cmds.entity(menu_id).despawn_recursive();
+            controls.save();
+            time.unpause();
+        }
+        return;
+    }
+
+    let text_style = |font_size| TextStyle {
+        font: menu_font.font(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    time.pause();
+    cmds.spawn((
+        ControlsMenu {
+            selected: 0,
+            is_rebinding: false,
+            status: String::new(),
+        },
+        StateScoped(GameState::Playing),
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.),
+                ..default()
+            },
+            background_color: Color::BLACK.with_alpha(0.8).into(),
+            z_index: CONTROLS_MENU_Z_INDEX,
+            ..default()
+        },
+    ))
+    .with_children(|menu| {
+        menu.spawn(TextBundle::from_section("Controls", text_style(60.)));
+        menu.spawn(TextBundle::from_section(
+            format!(
+                "[Enter/South] Rebind    [{close_key}/{CONTROLS_MENU_BUTTON:?}] Close",
+                close_key = key_label(CONTROLS_MENU_KEY)
+            ),
+            text_style(24.),
+        ));
+        for row in 0..controls.bindings.len() {
+            menu.spawn((
+                ControlsMenuRow(row),
+                TextBundle::from_section(String::new(), text_style(36.)),
+            ));
+        }
+        menu.spawn((
+            ControlsMenuStatus,
+            TextBundle::from_section(String::new(), text_style(28.)),
+        ));
+    });
+}
+
+fn navigate_controls_menu(
+    menu_in: MenuInput,
+    mut menu_qry: Query<&mut ControlsMenu>,
+    mut controls: ResMut<Controls>,
+) {
+    let Ok(mut menu) = menu_qry.get_single_mut() else {
+        return;
+    };
+
+    if menu.is_rebinding {
+        if menu_in.just_pressed(KeyCode::Escape, GamepadButtonType::East) {
+            menu.is_rebinding = false;
+            menu.status.clear();
+            return;
+        }
+        let Some(key) = menu_in.just_pressed_key() else {
+            return;
+        };
+        menu.is_rebinding = false;
+        if key == CONTROLS_MENU_KEY {
+            menu.status.clear();
+            return;
+        }
+
+        let action = controls.bindings[menu.selected].action.clone();
+        menu.status = match controls.rebind(&action, key) {
+            Ok(()) => format!("{action:?} bound to {}", key_label(key)),
+            Err(conflict) => format!("{} is already bound to {conflict:?}", key_label(key)),
+        };
+    } else if menu_in.just_pressed(KeyCode::ArrowUp, GamepadButtonType::DPadUp) {
+        menu.selected = menu.selected.saturating_sub(1);
+    } else if menu_in.just_pressed(KeyCode::ArrowDown, GamepadButtonType::DPadDown) {
+        menu.selected = (menu.selected + 1).min(controls.bindings.len() - 1);
+    } else if menu_in.just_pressed(KeyCode::Enter, GamepadButtonType::South) {
+        menu.is_rebinding = true;
+        menu.status = String::from("Press a key, or Escape to cancel");
+    }
+}
+
+fn update_controls_menu(
+    menu_qry: Query<&ControlsMenu>,
+    mut row_qry: Query<(&ControlsMenuRow, &mut Text), Without<ControlsMenuStatus>>,
+    mut status_qry: Query<&mut Text, With<ControlsMenuStatus>>,
+    controls: Res<Controls>,
+) {
+    let Ok(menu) = menu_qry.get_single() else {
+        return;
+    };
+
+    for (&ControlsMenuRow(row), mut row_text) in &mut row_qry {
+        let binding = &controls.bindings[row];
+        row_text.sections[0].value = format!(
+            "{action:?}: {key}",
+            action = binding.action,
+            key = if menu.is_rebinding && menu.selected == row {
+                String::from("...")
+            } else {
+                key_label(binding.key)
+            }
+        );
+        row_text.sections[0].style.color = if menu.selected == row {
+            Color::srgb(1., 0.8, 0.)
+        } else {
+            Color::WHITE
+        };
+    }
+    for mut status_text in &mut status_qry {
+        status_text.sections[0].value.clone_from(&menu.status);
+    }
+}
+
+fn apply_gamepad_deadzone(mut gamepad_settings: ResMut<GamepadSettings>) {
+    let axis_settings = &mut gamepad_settings.default_axis_settings;
+    axis_settings.set_deadzone_lowerbound(-GAMEPAD_DEADZONE);
+    axis_settings.set_deadzone_upperbound(GAMEPAD_DEADZONE);
+}
+
+/// Only the gilrs backend applies [`GamepadSettings`] to what it reads, so sticks are
+/// clamped again before any action is derived from them, whatever reported them.
+fn enforce_gamepad_deadzone(
+    gamepad_settings: Res<GamepadSettings>,
+    mut gamepad_axes: ResMut<Axis<GamepadAxis>>,
+) {
+    let axes = gamepad_axes.devices().copied().collect::<Vec<_>>();
+    for axis in axes {
+        if let Some(value) = gamepad_axes.get_unclamped(axis) {
+            gamepad_axes.set(axis, gamepad_settings.get_axis_settings(axis).clamp(value));
+        }
+    }
+}
+
+fn apply_controls(
+    controls: Res<Controls>,
+    mut input_map_qry: Query<(&mut InputMap<PlayerAction>, &InputDevices)>,
+) {
+    if !controls.is_changed() {
+        return;
+    }
+    for (mut input_map, &devices) in &mut input_map_qry {
+        *input_map = controls.input_map(devices);
+    }
+}
+
+pub fn controls_plugin(app: &mut App) {
+    app.insert_resource(Controls::load())
+        .add_systems(Startup, apply_gamepad_deadzone)
+        .add_systems(
+            PreUpdate,
+            enforce_gamepad_deadzone
+                .after(InputSystem)
+                .before(InputManagerSystem::Update),
+        )
+        .add_systems(
+            OnEnter(GameState::Setup),
+            |mut cmds: Commands, asset_server: Res<AssetServer>| {
+                cmds.insert_resource(FontOwner::<ControlsMenu>::new(
+                    asset_server.load("font.ttf"),
+                ));
+            },
+        )
+        .add_systems(
+            OnExit(GameState::Playing),
+            |mut time: ResMut<Time<Virtual>>| time.unpause(),
+        )
+        .add_systems(
+            Update,
+            (
+                toggle_controls_menu,
+                navigate_controls_menu,
+                update_controls_menu,
+                apply_controls,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::testing::{self, gamepad, is_pressed},
+    };
+
+    const STICK_PAST_DEADZONE: f32 = 0.8;
+
+    fn test_app() -> App {
+        let mut app = testing::input_app();
+        app.add_systems(Startup, apply_gamepad_deadzone)
+            .add_systems(
+                PreUpdate,
+                enforce_gamepad_deadzone
+                    .after(InputSystem)
+                    .before(InputManagerSystem::Update),
+            )
+            .add_systems(Update, navigate_controls_menu);
+        app.update();
+        app
+    }
+
+    fn spawn_player_input(app: &mut App) -> Entity {
+        app.world_mut()
+            .spawn(testing::player_input(InputDevices::Gamepad(gamepad())))
+            .id()
+    }
+
+    fn set_button(app: &mut App, button: GamepadButtonType, is_pressed: bool) {
+        testing::set_button(app, button, is_pressed);
+        app.update();
+    }
+
+    fn set_axis(app: &mut App, axis: GamepadAxisType, value: f32) {
+        testing::set_axis(app, axis, value);
+        app.update();
+    }
+
+    /// The deadzone that [`apply_gamepad_deadzone`] configured, rather than the constant.
+    fn deadzone(app: &App) -> f32 {
+        app.world()
+            .resource::<GamepadSettings>()
+            .default_axis_settings
+            .deadzone_upperbound()
+    }
+
+    fn spawn_menu(app: &mut App) -> Entity {
+        app.world_mut()
+            .spawn(ControlsMenu {
+                selected: 0,
+                is_rebinding: false,
+                status: String::new(),
+            })
+            .id()
+    }
+
+    fn menu(app: &App, menu_id: Entity) -> &ControlsMenu {
+        app.world().get::<ControlsMenu>(menu_id).unwrap()
+    }
+
+    #[test]
+    fn deadzone_is_configured() {
+        let app = test_app();
+        assert_eq!(deadzone(&app), GAMEPAD_DEADZONE);
+    }
+
+    #[test]
+    fn stick_inside_deadzone_does_not_move() {
+        let mut app = test_app();
+        let player_id = spawn_player_input(&mut app);
+        let inside_deadzone = deadzone(&app) * 0.9;
+        set_axis(&mut app, GamepadAxisType::LeftStickX, inside_deadzone);
+        assert!(!is_pressed(&app, player_id, PlayerAction::MoveRight));
+        set_axis(&mut app, GamepadAxisType::LeftStickX, -inside_deadzone);
+        assert!(!is_pressed(&app, player_id, PlayerAction::MoveLeft));
+        set_axis(&mut app, GamepadAxisType::LeftStickY, -inside_deadzone);
+        assert!(!is_pressed(&app, player_id, PlayerAction::DropDown));
+    }
+
+    #[test]
+    fn stick_past_deadzone_moves() {
+        let mut app = test_app();
+        let player_id = spawn_player_input(&mut app);
+        set_axis(&mut app, GamepadAxisType::LeftStickX, STICK_PAST_DEADZONE);
+        assert!(is_pressed(&app, player_id, PlayerAction::MoveRight));
+        assert!(!is_pressed(&app, player_id, PlayerAction::MoveLeft));
+        set_axis(&mut app, GamepadAxisType::LeftStickX, -STICK_PAST_DEADZONE);
+        assert!(is_pressed(&app, player_id, PlayerAction::MoveLeft));
+        assert!(!is_pressed(&app, player_id, PlayerAction::MoveRight));
+    }
+
+    #[test]
+    fn stick_down_drops_down() {
+        let mut app = test_app();
+        let player_id = spawn_player_input(&mut app);
+        set_axis(&mut app, GamepadAxisType::LeftStickY, -STICK_PAST_DEADZONE);
+        assert!(is_pressed(&app, player_id, PlayerAction::DropDown));
+    }
+
+    #[test]
+    fn buttons_trigger_actions() {
+        let mut app = test_app();
+        let player_id = spawn_player_input(&mut app);
+        for (action, button) in [
+            (PlayerAction::Jump, GamepadButtonType::South),
+            (PlayerAction::EnterDoor, GamepadButtonType::West),
+            (PlayerAction::DropDown, GamepadButtonType::DPadDown),
+        ] {
+            set_button(&mut app, button, true);
+            assert!(is_pressed(&app, player_id, action.clone()), "{action:?}");
+            set_button(&mut app, button, false);
+            assert!(!is_pressed(&app, player_id, action.clone()), "{action:?}");
+        }
+    }
+
+    #[test]
+    fn dpad_navigates_menu() {
+        let mut app = test_app();
+        let menu_id = spawn_menu(&mut app);
+        set_button(&mut app, GamepadButtonType::DPadDown, true);
+        set_button(&mut app, GamepadButtonType::DPadDown, false);
+        set_button(&mut app, GamepadButtonType::DPadDown, true);
+        set_button(&mut app, GamepadButtonType::DPadDown, false);
+        assert_eq!(menu(&app, menu_id).selected, 2);
+        set_button(&mut app, GamepadButtonType::DPadUp, true);
+        assert_eq!(menu(&app, menu_id).selected, 1);
+    }
+
+    #[test]
+    fn gamepad_starts_and_cancels_rebinding() {
+        let mut app = test_app();
+        let menu_id = spawn_menu(&mut app);
+        set_button(&mut app, GamepadButtonType::South, true);
+        assert!(menu(&app, menu_id).is_rebinding);
+        set_button(&mut app, GamepadButtonType::East, true);
+        assert!(!menu(&app, menu_id).is_rebinding);
+    }
+
+    #[test]
+    fn prompt_label_follows_devices() {
+        let controls = Controls::default();
+        let action = PlayerAction::EnterDoor;
+        assert_eq!(
+            controls.action_label(&action, InputDevices::KeyboardAndMouse),
+            "Space"
+        );
+        assert_eq!(
+            controls.action_label(&action, InputDevices::Gamepad(gamepad())),
+            "West"
+        );
+        assert_eq!(
+            controls.action_label(&action, InputDevices::All),
+            "Space/West"
+        );
+    }
+}
@@ -1,11 +1,13 @@
 use {
     super::{
         asset_owner::TextureAtlasOwner,
-        level,
+        interaction::{Interact, Interactable},
+        level::{self, Destination, LevelInfo},
+        player::{DoorTransit, Dying, Player, DOOR_TRANSIT_DURATION},
         tile::{Tile, TILE_SIZE, TILE_Z},
     },
     crate::GameState,
-    bevy::prelude::*,
+    bevy::{ecs::system::SystemId, prelude::*},
     bevy_rapier2d::prelude::*,
     bevy_tnua::TnuaGhostPlatform,
 };
@@ -16,14 +18,30 @@ const DOOR_PLATFORM_SIZE: Vec2 = Vec2::new(TILE_SIZE.x, TILE_SIZE.y / 6.);
 #[derive(Component, PartialEq, Eq, Clone, Copy)]
 pub enum Door {
     Entrance,
-    Exit,
+    Exit(Destination),
 }
 
+#[derive(Component)]
+pub struct Locked {
+    pub unlocked_tex_idx: usize,
+}
+
+/// Sent when a player opens an exit, which takes everyone else along.
+#[derive(Event)]
+pub struct DoorEnterEvent {
+    pub player: Entity,
+    pub door: Door,
+}
+
+#[derive(Resource)]
+struct DoorInteraction(SystemId<Interact>);
+
 #[derive(Event)]
 pub struct DoorSpawnEvent {
     pub pos: Vec2,
     pub tex_idx: usize,
-    pub is_exit: bool,
+    pub door: Door,
+    pub locked_tex_idx: Option<usize>,
 }
 
 fn on_door_spawn(
@@ -34,11 +52,12 @@ fn on_door_spawn(
     for &DoorSpawnEvent {
         pos,
         tex_idx,
-        is_exit,
+        door,
+        locked_tex_idx,
     } in door_spawn_evr.read()
     {
-        cmds.spawn((
-            if is_exit { Door::Exit } else { Door::Entrance },
+        let mut door_cmds = cmds.spawn((
+            door,
             StateScoped(GameState::Playing),
             SpriteBundle {
                 transform: Transform::from_translation(pos.extend(DOOR_Z)),
@@ -47,11 +66,16 @@ fn on_door_spawn(
             },
             TextureAtlas {
                 layout: tile_assets.layout(),
-                index: tex_idx,
+                index: locked_tex_idx.unwrap_or(tex_idx),
             },
             Collider::cuboid(TILE_SIZE.x / 2., TILE_SIZE.y / 2.),
             Sensor,
         ));
+        if locked_tex_idx.is_some() {
+            door_cmds.insert(Locked {
+                unlocked_tex_idx: tex_idx,
+            });
+        }
 
         cmds.spawn((
             StateScoped(GameState::Playing),
@@ -76,9 +100,62 @@ fn on_door_spawn(
     }
 }
 
+fn make_exits_interactable(
+    door_qry: Query<(Entity, &Door), (Without<Locked>, Without<Interactable>)>,
+    door_interaction: Res<DoorInteraction>,
+    mut cmds: Commands,
+) {
+    for (door_id, door) in &door_qry {
+        if let Door::Exit(_) = door {
+            cmds.entity(door_id)
+                .insert(Interactable::new("Enter", door_interaction.0));
+        }
+    }
+}
+
+fn enter_door(
+    In(Interact {
+        interactor,
+        interactable,
+    }): In<Interact>,
+    door_qry: Query<(&Door, &Transform)>,
+    player_qry: Query<(Entity, Has<DoorTransit>), (With<Player>, Without<Dying>)>,
+    mut level_info: ResMut<LevelInfo>,
+    mut door_enter_evw: EventWriter<DoorEnterEvent>,
+    mut cmds: Commands,
+) {
+    let Ok((&door @ Door::Exit(destination), door_xform)) = door_qry.get(interactable) else {
+        return;
+    };
+    let Ok((_, false)) = player_qry.get(interactor) else {
+        return;
+    };
+    level_info.set_destination(destination);
+    door_enter_evw.send(DoorEnterEvent {
+        player: interactor,
+        door,
+    });
+    // The door is shared: everyone still standing goes through with whoever opened it.
+    for (player_id, _) in &player_qry {
+        cmds.entity(player_id).insert(DoorTransit::Entering {
+            door_x: door_xform.translation.x,
+            timer: Timer::new(DOOR_TRANSIT_DURATION, TimerMode::Once),
+        });
+    }
+}
+
 pub fn door_plugin(app: &mut App) {
-    app.add_event::<DoorSpawnEvent>().add_systems(
-        OnEnter(GameState::Playing),
-        (on_door_spawn.after(level::signal_level_object_spawns)).chain(),
-    );
+    let door_interaction = DoorInteraction(app.world_mut().register_system(enter_door));
+
+    app.add_event::<DoorSpawnEvent>()
+        .add_event::<DoorEnterEvent>()
+        .insert_resource(door_interaction)
+        .add_systems(
+            OnEnter(GameState::Playing),
+            (on_door_spawn.after(level::signal_level_object_spawns)).chain(),
+        )
+        .add_systems(
+            FixedUpdate,
+            make_exits_interactable.run_if(in_state(GameState::Playing)),
+        );
 }
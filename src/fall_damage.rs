@@ -0,0 +1,190 @@
+use {
+    super::{
+        combat::{DamageEvent, DamageKind, DeathEvent, Health, Iframes, LastHitBy},
+        item::Inventory,
+        level::MAX_REQUIRED_DROP,
+        movement::MovementTuning,
+        player::{Dying, Player},
+        tile::{TILE_SIZE, TILE_Z},
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    bevy_tnua::prelude::*,
+    std::time::Duration,
+};
+
+const FALL_HEIGHT_PER_DAMAGE: f32 = TILE_SIZE.y;
+const FALL_DAMAGE_SOURCE: &str = "Fall";
+const LANDING_STUN_DURATION: Duration = Duration::from_millis(400);
+const FALL_IFRAMES_DURATION: Duration = Duration::from_millis(500);
+const DUST_Z: f32 = TILE_Z + 2.5;
+const DUST_PARTICLE_COUNT: usize = 6;
+const DUST_PARTICLE_SIZE: f32 = 12.;
+const DUST_SPEED: f32 = 2. * TILE_SIZE.x;
+const DUST_DURATION: Duration = Duration::from_millis(300);
+
+/// The longest fall that does no damage. Leaves room for jumping into a required drop from
+/// as high as the player can get, with every air jump.
+fn safe_fall_height(tuning: &MovementTuning, inventory: &Inventory) -> f32 {
+    let max_jump_rise = tuning.jump_height
+        * inventory.jump_height_factor()
+        * (1 + tuning.air_jumps + inventory.extra_air_jumps()) as f32;
+    (MAX_REQUIRED_DROP + max_jump_rise) * TILE_SIZE.y
+}
+
+#[derive(Component, Default)]
+pub struct FallTracker {
+    /// Downward speed during the last tick spent airborne.
+    fall_speed: f32,
+    is_airborne: bool,
+}
+
+/// Present while the player recovers from a hard landing.
+#[derive(Component)]
+pub struct LandingStun(Timer);
+
+#[derive(Component)]
+struct Dust {
+    timer: Timer,
+    vel: Vec2,
+}
+
+fn track_falls(
+    mut player_qry: Query<
+        (
+            Entity,
+            &mut FallTracker,
+            &TnuaController,
+            &Velocity,
+            &Transform,
+            (&MovementTuning, &Inventory),
+            &mut Health,
+            Has<Iframes>,
+        ),
+        (With<Player>, Without<Dying>),
+    >,
+    rapier_cfg: Res<RapierConfiguration>,
+    mut dmg_evw: EventWriter<DamageEvent>,
+    mut death_evw: EventWriter<DeathEvent>,
+    mut cmds: Commands,
+) {
+    for (
+        player_id,
+        mut player_fall,
+        player_kcc,
+        player_velocity,
+        player_xform,
+        (player_tuning, player_inventory),
+        mut player_hp,
+        player_is_invulnerable,
+    ) in &mut player_qry
+    {
+        let Some((_, basis_state)) = player_kcc.concrete_basis::<TnuaBuiltinWalk>() else {
+            continue;
+        };
+        let was_airborne = player_fall.is_airborne;
+        player_fall.is_airborne = basis_state.standing_on_entity().is_none();
+        if player_fall.is_airborne {
+            player_fall.fall_speed = -player_velocity.linvel.y;
+            continue;
+        }
+        if !was_airborne {
+            continue;
+        }
+
+        // The height a free fall would need to reach the speed the player landed at.
+        let fall_height = player_fall.fall_speed.max(0.).powi(2) / (2. * -rapier_cfg.gravity.y);
+        let safe_fall_height = safe_fall_height(player_tuning, player_inventory);
+        if fall_height <= safe_fall_height {
+            continue;
+        }
+
+        cmds.entity(player_id).insert(LandingStun(Timer::new(
+            LANDING_STUN_DURATION,
+            TimerMode::Once,
+        )));
+        // Like any other damage, a fall cannot hurt a player with iframes, e.g. from a dash.
+        if !player_is_invulnerable {
+            let dmg = ((fall_height - safe_fall_height) / FALL_HEIGHT_PER_DAMAGE).ceil() as i8;
+            if player_hp.take(dmg) {
+                death_evw.send(DeathEvent {
+                    entity: player_id,
+                    source: None,
+                    source_name: Some(String::from(FALL_DAMAGE_SOURCE)),
+                });
+            }
+            dmg_evw.send(DamageEvent {
+                target: player_id,
+                source: None,
+                source_name: Some(String::from(FALL_DAMAGE_SOURCE)),
+                amount: dmg,
+                kind: DamageKind::Fall,
+            });
+            cmds.entity(player_id).insert((
+                LastHitBy(String::from(FALL_DAMAGE_SOURCE)),
+                Iframes::new(FALL_IFRAMES_DURATION),
+            ));
+        }
+
+        let feet_pos = player_xform.translation.truncate()
+            - player_tuning.float_height * TILE_SIZE.y * Vec2::Y;
+        for i in 0..DUST_PARTICLE_COUNT {
+            let dir = if i % 2 == 0 { -1. } else { 1. };
+            let spread = (i / 2 + 1) as f32 / (DUST_PARTICLE_COUNT / 2) as f32;
+            cmds.spawn((
+                Dust {
+                    timer: Timer::new(DUST_DURATION, TimerMode::Once),
+                    vel: Vec2::new(dir * DUST_SPEED * spread, DUST_SPEED * (1. - spread) / 2.),
+                },
+                StateScoped(GameState::Playing),
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgb(0.6, 0.5, 0.4),
+                        custom_size: Some(Vec2::splat(DUST_PARTICLE_SIZE)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(feet_pos.extend(DUST_Z)),
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+fn update_landing_stuns(
+    time: Res<Time>,
+    mut player_qry: Query<(Entity, &mut LandingStun)>,
+    mut cmds: Commands,
+) {
+    for (player_id, mut player_stun) in &mut player_qry {
+        if player_stun.0.tick(time.delta()).finished() {
+            cmds.entity(player_id).remove::<LandingStun>();
+        }
+    }
+}
+
+fn update_dust(
+    time: Res<Time>,
+    mut dust_qry: Query<(Entity, &mut Dust, &mut Transform, &mut Sprite)>,
+    mut cmds: Commands,
+) {
+    for (dust_id, mut dust, mut dust_xform, mut dust_sprite) in &mut dust_qry {
+        dust.timer.tick(time.delta());
+        dust_xform.translation += (dust.vel * time.delta_seconds()).extend(0.);
+        dust_sprite.color.set_alpha(dust.timer.fraction_remaining());
+        if dust.timer.finished() {
+            cmds.entity(dust_id).despawn_recursive();
+        }
+    }
+}
+
+pub fn fall_damage_plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        (track_falls, update_landing_stuns, update_dust)
+            .chain()
+            .after(TnuaUserControlsSystemSet)
+            .run_if(in_state(GameState::Playing)),
+    );
+}
@@ -0,0 +1,121 @@
+use {
+    super::{
+        asset_owner::FontOwner,
+        controls::{key_label, MenuInput},
+        level::LevelInfo,
+        run_state::RunState,
+        stats::{self, LifetimeStats},
+    },
+    crate::GameState,
+    bevy::{app::AppExit, prelude::*},
+};
+
+const RESTART_KEY: KeyCode = KeyCode::KeyR;
+const RESTART_BUTTON: GamepadButtonType = GamepadButtonType::South;
+const QUIT_KEY: KeyCode = KeyCode::Escape;
+const QUIT_BUTTON: GamepadButtonType = GamepadButtonType::East;
+
+#[derive(Component)]
+struct GameOverScreen;
+
+#[derive(Resource)]
+pub struct GameOverInfo {
+    pub cause: String,
+}
+
+fn spawn_game_over_screen(
+    mut cmds: Commands,
+    game_over_font: Res<FontOwner<GameOverScreen>>,
+    game_over_info: Res<GameOverInfo>,
+    level_info: Res<LevelInfo>,
+    run_state: Option<Res<RunState>>,
+    lifetime_stats: Res<LifetimeStats>,
+) {
+    let text_style = |font_size| TextStyle {
+        font: game_over_font.font(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    cmds.spawn((
+        GameOverScreen,
+        StateScoped(GameState::GameOver),
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(20.),
+                ..default()
+            },
+            background_color: Color::BLACK.into(),
+            ..default()
+        },
+    ))
+    .with_children(|screen| {
+        screen.spawn(TextBundle::from_section("Game Over", text_style(80.)));
+        screen.spawn(TextBundle::from_section(
+            format!("Killed by {}", game_over_info.cause),
+            text_style(40.),
+        ));
+        screen.spawn(TextBundle::from_section(
+            format!("Reached {level_info}"),
+            text_style(40.),
+        ));
+        if let Some(run_state) = &run_state {
+            screen.spawn(TextBundle::from_section(
+                format!("Collected {} gold", run_state.gold),
+                text_style(40.),
+            ));
+            screen.spawn(
+                TextBundle::from_section(run_state.stats.summary(), text_style(24.))
+                    .with_text_justify(JustifyText::Center),
+            );
+        }
+        screen.spawn(TextBundle::from_section(
+            lifetime_stats.summary(),
+            text_style(24.),
+        ));
+        screen.spawn(TextBundle::from_section(
+            format!(
+                "[{restart_key}/{RESTART_BUTTON:?}] Restart    [{quit_key}/{QUIT_BUTTON:?}] Quit",
+                restart_key = key_label(RESTART_KEY),
+                quit_key = key_label(QUIT_KEY)
+            ),
+            text_style(32.),
+        ));
+    });
+}
+
+fn handle_game_over_input(
+    menu_in: MenuInput,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut app_exit_evw: EventWriter<AppExit>,
+) {
+    if menu_in.just_pressed(RESTART_KEY, RESTART_BUTTON) {
+        next_state.set(GameState::CharacterSelect);
+    } else if menu_in.just_pressed(QUIT_KEY, QUIT_BUTTON) {
+        app_exit_evw.send(AppExit::Success);
+    }
+}
+
+pub fn game_over_plugin(app: &mut App) {
+    app.add_systems(
+        OnEnter(GameState::Setup),
+        |mut cmds: Commands, asset_server: Res<AssetServer>| {
+            cmds.insert_resource(FontOwner::<GameOverScreen>::new(
+                asset_server.load("font.ttf"),
+            ));
+        },
+    )
+    .add_systems(
+        OnEnter(GameState::GameOver),
+        spawn_game_over_screen.after(stats::record_lifetime_stats),
+    )
+    .add_systems(
+        Update,
+        handle_game_over_input.run_if(in_state(GameState::GameOver)),
+    );
+}
@@ -0,0 +1,301 @@
+use {
+    super::{
+        animation::{AnimationIndices, AnimationTimer},
+        character::{Character, CharacterAtlases, SelectedCharacter},
+        level::{LevelInfo, RunSeed, GENERATOR_VERSION},
+        player::{self, DoorTransit, Player, PlayerAnimation},
+        sprite_flip::Flippable,
+        tile::TILE_Z,
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    bevy_tnua::TnuaAnimatingState,
+    serde::{Deserialize, Serialize},
+    std::{fs, mem},
+};
+
+const GHOSTS_PATH: &str = "ghosts.ron";
+/// Where an unreadable [`GHOSTS_PATH`] is moved, rather than being overwritten.
+const INVALID_GHOSTS_PATH: &str = "ghosts.invalid.ron";
+/// Older runs are dropped first to keep [`GHOSTS_PATH`] from growing without bound.
+const MAX_BEST_RUNS: usize = 64;
+/// About five minutes of fixed ticks; slower runs are not worth racing.
+const MAX_GHOST_FRAMES: usize = 64 * 60 * 5;
+const GHOST_Z: f32 = TILE_Z + 1.5;
+const GHOST_ALPHA: f32 = 0.4;
+
+/// The player's state during one fixed tick.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct GhostFrame {
+    pos: Vec2,
+    animation: PlayerAnimation,
+    flip_x: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BestRun {
+    /// The [`GENERATOR_VERSION`] the level was played on, `0` from before it existed.
+    #[serde(default)]
+    generator_version: u32,
+    seed: u64,
+    level: String,
+    #[serde(default)]
+    character: Character,
+    frames: Vec<GhostFrame>,
+}
+
+/// The fastest run through every level played so far, persisted to [`GHOSTS_PATH`].
+#[derive(Resource, Serialize, Deserialize, Default)]
+struct BestRuns {
+    /// Oldest first.
+    runs: Vec<BestRun>,
+    /// Set when an invalid [`GHOSTS_PATH`] could not be moved out of the way.
+    #[serde(skip)]
+    is_read_only: bool,
+}
+
+impl BestRuns {
+    fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(GHOSTS_PATH) else {
+            return Self::default();
+        };
+        match ron::from_str::<Self>(&contents) {
+            Ok(mut best_runs) => {
+                // Runs on levels generated differently would race through walls.
+                best_runs
+                    .runs
+                    .retain(|run| run.generator_version == GENERATOR_VERSION);
+                best_runs
+            }
+            Err(err) => {
+                warn!("ignoring invalid {GHOSTS_PATH}: {err}");
+                match fs::rename(GHOSTS_PATH, INVALID_GHOSTS_PATH) {
+                    Ok(()) => Self::default(),
+                    Err(err) => {
+                        error!("failed to move {GHOSTS_PATH} to {INVALID_GHOSTS_PATH}: {err}");
+                        Self {
+                            is_read_only: true,
+                            ..default()
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn save(&self) {
+        if self.is_read_only {
+            return;
+        }
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(GHOSTS_PATH, contents).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            error!("failed to save {GHOSTS_PATH}: {err}");
+        }
+    }
+
+    fn get(&self, seed: u64, level: &str) -> Option<&BestRun> {
+        self.runs
+            .iter()
+            .find(|run| run.seed == seed && run.level == level)
+    }
+}
+
+#[derive(Resource, Default)]
+struct CurrentRun {
+    level: String,
+    character: Character,
+    frames: Vec<GhostFrame>,
+    is_finished: bool,
+}
+
+/// How far ahead (negative) or behind (positive) the best run the level was finished, in seconds.
+#[derive(Resource)]
+pub struct LevelSplit(pub f32);
+
+#[derive(Component)]
+struct Ghost {
+    character: Character,
+    frames: Vec<GhostFrame>,
+    tick: usize,
+}
+
+fn start_level_run(
+    mut cmds: Commands,
+    best_runs: Res<BestRuns>,
+    run_seed: Res<RunSeed>,
+    level_info: Res<LevelInfo>,
+    character_atlases: Res<CharacterAtlases>,
+    selected_character: Res<SelectedCharacter>,
+) {
+    let level = level_info.to_string();
+    cmds.remove_resource::<LevelSplit>();
+
+    // Runs saved without frames have nothing to show.
+    if let Some((best_run, &first_frame)) = best_runs
+        .get(run_seed.0, &level)
+        .and_then(|best_run| Some((best_run, best_run.frames.first()?)))
+    {
+        let (first_animation_idxs, first_animation_timer) =
+            best_run.character.animation(first_frame.animation);
+        let ghost_assets = character_atlases.get(best_run.character);
+        cmds.spawn((
+            Ghost {
+                character: best_run.character,
+                frames: best_run.frames.clone(),
+                tick: 0,
+            },
+            StateScoped(GameState::Playing),
+            first_animation_idxs,
+            first_animation_timer,
+            Flippable {
+                flip_x: first_frame.flip_x,
+                ..default()
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::WHITE.with_alpha(GHOST_ALPHA),
+                    ..default()
+                },
+                texture: ghost_assets.texture(),
+                transform: Transform::from_translation(first_frame.pos.extend(GHOST_Z)),
+                ..default()
+            },
+            TextureAtlas {
+                layout: ghost_assets.layout(),
+                index: 0,
+            },
+        ));
+    }
+
+    cmds.insert_resource(CurrentRun {
+        level,
+        character: selected_character.0,
+        ..default()
+    });
+}
+
+fn record_current_run(
+    mut current_run: ResMut<CurrentRun>,
+    player_qry: Query<(&Transform, &TnuaAnimatingState<PlayerAnimation>, &Flippable), With<Player>>,
+) {
+    let Ok((player_xform, player_animating_state, player_flippable)) = player_qry.get_single()
+    else {
+        return;
+    };
+    if current_run.is_finished {
+        return;
+    }
+
+    current_run.frames.push(GhostFrame {
+        pos: player_xform.translation.truncate(),
+        animation: player_animating_state
+            .get()
+            .copied()
+            .unwrap_or(PlayerAnimation::ExitingDoor),
+        flip_x: player_flippable.flip_x,
+    });
+}
+
+fn finish_current_run(
+    mut cmds: Commands,
+    mut current_run: ResMut<CurrentRun>,
+    mut best_runs: ResMut<BestRuns>,
+    run_seed: Res<RunSeed>,
+    time: Res<Time<Fixed>>,
+    player_qry: Query<&DoorTransit, (With<Player>, Added<DoorTransit>)>,
+) {
+    let Ok(DoorTransit::Entering { .. }) = player_qry.get_single() else {
+        return;
+    };
+    current_run.is_finished = true;
+
+    let frames = mem::take(&mut current_run.frames);
+    match best_runs.get(run_seed.0, &current_run.level) {
+        Some(best_run) => {
+            let tick_delta = frames.len() as f32 - best_run.frames.len() as f32;
+            cmds.insert_resource(LevelSplit(tick_delta * time.timestep().as_secs_f32()));
+            if tick_delta >= 0. {
+                return;
+            }
+            best_runs
+                .runs
+                .retain(|run| run.seed != run_seed.0 || run.level != current_run.level);
+        }
+        // Beating a saved run keeps within the cap, since saved runs are within it.
+        None if frames.is_empty() || frames.len() > MAX_GHOST_FRAMES => return,
+        None => (),
+    }
+
+    if best_runs.runs.len() >= MAX_BEST_RUNS {
+        let excess = best_runs.runs.len() + 1 - MAX_BEST_RUNS;
+        best_runs.runs.drain(..excess);
+    }
+    best_runs.runs.push(BestRun {
+        generator_version: GENERATOR_VERSION,
+        seed: run_seed.0,
+        level: current_run.level.clone(),
+        character: current_run.character,
+        frames,
+    });
+    best_runs.save();
+}
+
+fn play_ghosts(
+    mut ghost_qry: Query<(
+        Entity,
+        &mut Ghost,
+        &mut Transform,
+        &mut Flippable,
+        &mut AnimationIndices,
+        &mut AnimationTimer,
+    )>,
+    mut cmds: Commands,
+) {
+    for (
+        ghost_id,
+        mut ghost,
+        mut ghost_xform,
+        mut ghost_flippable,
+        mut ghost_animation_idxs,
+        mut ghost_animation_timer,
+    ) in &mut ghost_qry
+    {
+        // The best run ended by walking through the exit door.
+        let Some(&frame) = ghost.frames.get(ghost.tick) else {
+            cmds.entity(ghost_id).despawn_recursive();
+            continue;
+        };
+
+        ghost_xform.translation = frame.pos.extend(GHOST_Z);
+        ghost_flippable.flip_x = frame.flip_x;
+        if ghost.tick > 0 && ghost.frames[ghost.tick - 1].animation != frame.animation {
+            (*ghost_animation_idxs, *ghost_animation_timer) =
+                ghost.character.animation(frame.animation);
+        }
+        ghost.tick += 1;
+    }
+}
+
+pub fn ghost_plugin(app: &mut App) {
+    app.insert_resource(BestRuns::load())
+        .init_resource::<CurrentRun>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            start_level_run.after(player::on_player_spawn),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                // Best runs are single player only.
+                (record_current_run, finish_current_run).run_if(player::is_single_player),
+                play_ghosts,
+            )
+                .chain()
+                .after(PhysicsSet::Writeback)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
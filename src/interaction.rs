@@ -0,0 +1,203 @@
+use {
+    super::{
+        asset_owner::FontOwner,
+        controls::{self, Controls, InputDevices},
+        player::{LedgeGrab, Player, PlayerAction, MAX_PLAYERS},
+        tile::TILE_SIZE,
+    },
+    crate::GameState,
+    bevy::{ecs::system::SystemId, prelude::*},
+    bevy_rapier2d::prelude::*,
+    leafwing_input_manager::prelude::*,
+};
+
+const PROMPT_Z: f32 = 10.;
+const PROMPT_OFFSET: Vec2 = Vec2::new(0., TILE_SIZE.y * 3. / 4.);
+const PROMPT_FONT_SIZE: f32 = 32.;
+
+/// Input handed to an [`Interactable`]'s action when it is triggered.
+#[derive(Clone, Copy)]
+pub struct Interact {
+    pub interactor: Entity,
+    pub interactable: Entity,
+}
+
+/// Something the player can trigger with [`PlayerAction::EnterDoor`] while overlapping
+/// its sensor. Only the nearest interactable in range is prompted and triggered.
+#[derive(Component)]
+pub struct Interactable {
+    pub prompt: String,
+    pub action: SystemId<Interact>,
+}
+
+impl Interactable {
+    pub fn new(prompt: impl Into<String>, action: SystemId<Interact>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            action,
+        }
+    }
+}
+
+/// Shows the player with this index how to trigger the interactable nearest to them.
+#[derive(Component)]
+struct InteractionPrompt(usize);
+
+fn nearest_interactable<'a>(
+    player_id: Entity,
+    player_pos: Vec2,
+    interactable_qry: &'a Query<(Entity, &Interactable, &GlobalTransform), With<Sensor>>,
+    rapier_ctx: &RapierContext,
+) -> Option<(Entity, &'a Interactable, Vec2)> {
+    interactable_qry
+        .iter()
+        .filter(|&(interactable_id, ..)| {
+            rapier_ctx.intersection_pair(player_id, interactable_id) == Some(true)
+        })
+        .map(|(interactable_id, interactable, interactable_glob_xform)| {
+            (
+                interactable_id,
+                interactable,
+                interactable_glob_xform.translation().truncate(),
+            )
+        })
+        .min_by(|(.., a), (.., b)| {
+            a.distance_squared(player_pos)
+                .total_cmp(&b.distance_squared(player_pos))
+        })
+}
+
+fn spawn_interaction_prompts(mut cmds: Commands, prompt_font: Res<FontOwner<InteractionPrompt>>) {
+    for player_idx in 0..MAX_PLAYERS {
+        cmds.spawn((
+            InteractionPrompt(player_idx),
+            StateScoped(GameState::Playing),
+            Text2dBundle {
+                text: Text::from_section(
+                    String::new(),
+                    TextStyle {
+                        font: prompt_font.font(),
+                        font_size: PROMPT_FONT_SIZE,
+                        color: Color::BLACK,
+                    },
+                ),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ));
+    }
+}
+
+fn update_interaction_prompts(
+    mut prompt_qry: Query<(
+        &InteractionPrompt,
+        &mut Text,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+    player_qry: Query<(&Player, Entity, &GlobalTransform, &InputDevices)>,
+    interactable_qry: Query<(Entity, &Interactable, &GlobalTransform), With<Sensor>>,
+    rapier_ctx: Res<RapierContext>,
+    controls: Res<Controls>,
+) {
+    let mut nearest_by_player = player_qry
+        .iter()
+        .filter_map(
+            |(&Player(player_idx), player_id, player_glob_xform, &player_devices)| {
+                nearest_interactable(
+                    player_id,
+                    player_glob_xform.translation().truncate(),
+                    &interactable_qry,
+                    &rapier_ctx,
+                )
+                .map(|nearest| (player_idx, player_devices, nearest))
+            },
+        )
+        .collect::<Vec<_>>();
+    nearest_by_player.sort_by_key(|&(player_idx, ..)| player_idx);
+
+    for (prompt, mut prompt_text, mut prompt_xform, mut prompt_visibility) in &mut prompt_qry {
+        let Some(nearest_idx) = nearest_by_player
+            .iter()
+            .position(|&(player_idx, ..)| player_idx == prompt.0)
+        else {
+            *prompt_visibility = Visibility::Hidden;
+            continue;
+        };
+        let (_, player_devices, (interactable_id, interactable, interactable_pos)) =
+            nearest_by_player[nearest_idx];
+        // Players near the same interactable get their prompts stacked instead of overlapping.
+        let stack_idx = nearest_by_player[..nearest_idx]
+            .iter()
+            .filter(|&&(_, _, (other_id, ..))| other_id == interactable_id)
+            .count();
+
+        prompt_text.sections[0].value = format!(
+            "[{key}] {prompt}",
+            key = controls.action_label(&PlayerAction::EnterDoor, player_devices),
+            prompt = interactable.prompt
+        );
+        prompt_xform.translation =
+            (interactable_pos + PROMPT_OFFSET + Vec2::Y * PROMPT_FONT_SIZE * stack_idx as f32)
+                .extend(PROMPT_Z);
+        *prompt_visibility = Visibility::Visible;
+    }
+}
+
+fn interact(
+    player_qry: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &ActionState<PlayerAction>,
+            &LedgeGrab,
+        ),
+        With<Player>,
+    >,
+    interactable_qry: Query<(Entity, &Interactable, &GlobalTransform), With<Sensor>>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+) {
+    for (player_id, player_glob_xform, player_in, player_ledge) in &player_qry {
+        // Hanging players have their hands full.
+        if !player_in.just_pressed(&PlayerAction::EnterDoor) || player_ledge.is_holding() {
+            continue;
+        }
+        if let Some((interactable_id, interactable, _)) = nearest_interactable(
+            player_id,
+            player_glob_xform.translation().truncate(),
+            &interactable_qry,
+            &rapier_ctx,
+        ) {
+            cmds.run_system_with_input(
+                interactable.action,
+                Interact {
+                    interactor: player_id,
+                    interactable: interactable_id,
+                },
+            );
+        }
+    }
+}
+
+pub fn interaction_plugin(app: &mut App) {
+    app.add_systems(
+        OnEnter(GameState::Setup),
+        |mut cmds: Commands, asset_server: Res<AssetServer>| {
+            cmds.insert_resource(FontOwner::<InteractionPrompt>::new(
+                asset_server.load("font.ttf"),
+            ));
+        },
+    )
+    .add_systems(OnEnter(GameState::Playing), spawn_interaction_prompts)
+    .add_systems(
+        FixedUpdate,
+        interact
+            .after(PhysicsSet::Writeback)
+            .run_if(in_state(GameState::Playing).and_then(controls::controls_menu_closed)),
+    )
+    .add_systems(
+        Update,
+        update_interaction_prompts.run_if(in_state(GameState::Playing)),
+    );
+}
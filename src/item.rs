@@ -0,0 +1,162 @@
+use {
+    super::{
+        asset_owner::TextureAtlasOwner,
+        combat::{HazardResistance, Health, MaxHealth},
+        level,
+        player::Player,
+        tile::{Tile, TILE_SIZE, TILE_Z},
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    serde::{Deserialize, Serialize},
+    static_assertions::const_assert,
+};
+
+const ITEM_Z: f32 = TILE_Z + 1.;
+const ITEM_SCALE: f32 = 0.5;
+const JUMP_HEIGHT_BONUS: f32 = 0.25;
+const RUN_SPEED_BONUS: f32 = 0.15;
+const HEART_CONTAINER_HEALTH: i8 = 2;
+
+const_assert!(HEART_CONTAINER_HEALTH > 0 && HEART_CONTAINER_HEALTH % 2 == 0);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ItemKind {
+    SpringBoots,
+    Feather,
+    HeartContainer,
+    SpikeGuard,
+    SpeedBoots,
+}
+
+impl ItemKind {
+    pub const ALL: [Self; 5] = [
+        Self::SpringBoots,
+        Self::Feather,
+        Self::HeartContainer,
+        Self::SpikeGuard,
+        Self::SpeedBoots,
+    ];
+
+    pub fn tex_idx(self) -> usize {
+        match self {
+            ItemKind::SpringBoots => 49,
+            ItemKind::Feather => 50,
+            ItemKind::HeartContainer => 67,
+            ItemKind::SpikeGuard => 51,
+            ItemKind::SpeedBoots => 52,
+        }
+    }
+}
+
+/// Passive upgrades picked up during a run.
+#[derive(Component, Clone, Default, Serialize, Deserialize)]
+pub struct Inventory {
+    items: Vec<ItemKind>,
+}
+
+impl Inventory {
+    pub fn items(&self) -> &[ItemKind] {
+        &self.items
+    }
+
+    fn count(&self, kind: ItemKind) -> usize {
+        self.items.iter().filter(|&&item| item == kind).count()
+    }
+
+    pub fn jump_height_factor(&self) -> f32 {
+        1. + JUMP_HEIGHT_BONUS * self.count(ItemKind::SpringBoots) as f32
+    }
+
+    pub fn extra_air_jumps(&self) -> usize {
+        self.count(ItemKind::Feather)
+    }
+
+    pub fn hazard_resistance(&self) -> i8 {
+        self.count(ItemKind::SpikeGuard) as i8
+    }
+
+    pub fn run_speed_factor(&self) -> f32 {
+        1. + RUN_SPEED_BONUS * self.count(ItemKind::SpeedBoots) as f32
+    }
+}
+
+#[derive(Component)]
+struct Item(ItemKind);
+
+#[derive(Event)]
+pub struct ItemSpawnEvent {
+    pub pos: Vec2,
+    pub kind: ItemKind,
+}
+
+fn on_item_spawn(
+    mut item_spawn_evr: EventReader<ItemSpawnEvent>,
+    mut cmds: Commands,
+    tile_assets: Res<TextureAtlasOwner<Tile>>,
+) {
+    for &ItemSpawnEvent { pos, kind } in item_spawn_evr.read() {
+        cmds.spawn((
+            Item(kind),
+            StateScoped(GameState::Playing),
+            SpriteBundle {
+                transform: Transform::from_translation(pos.extend(ITEM_Z))
+                    .with_scale(Vec3::splat(ITEM_SCALE)),
+                texture: tile_assets.texture(),
+                ..default()
+            },
+            TextureAtlas {
+                layout: tile_assets.layout(),
+                index: kind.tex_idx(),
+            },
+            Collider::cuboid(TILE_SIZE.x / 2., TILE_SIZE.y / 2.),
+            Sensor,
+        ));
+    }
+}
+
+fn pick_up_items(
+    item_qry: Query<(Entity, &Item)>,
+    mut player_qry: Query<(Entity, &mut Inventory, &mut Health, &mut MaxHealth), With<Player>>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+) {
+    for (player_id, mut player_inventory, mut player_hp, mut player_max_hp) in &mut player_qry {
+        for (item_id, &Item(kind)) in &item_qry {
+            if rapier_ctx.intersection_pair(player_id, item_id) == Some(true) {
+                player_inventory.items.push(kind);
+                if kind == ItemKind::HeartContainer {
+                    player_max_hp.0 += HEART_CONTAINER_HEALTH;
+                    player_hp.0 += HEART_CONTAINER_HEALTH;
+                }
+                cmds.entity(item_id).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn apply_inventories(
+    inventory_qry: Query<(Entity, &Inventory), Changed<Inventory>>,
+    mut cmds: Commands,
+) {
+    for (id, inventory) in &inventory_qry {
+        cmds.entity(id)
+            .insert(HazardResistance(inventory.hazard_resistance()));
+    }
+}
+
+pub fn item_plugin(app: &mut App) {
+    app.add_event::<ItemSpawnEvent>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            on_item_spawn.after(level::signal_level_object_spawns),
+        )
+        .add_systems(
+            FixedUpdate,
+            (pick_up_items, apply_inventories)
+                .chain()
+                .after(PhysicsSet::Writeback)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
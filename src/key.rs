@@ -0,0 +1,124 @@
+use {
+    super::{
+        asset_owner::TextureAtlasOwner,
+        door::{Door, Locked},
+        level,
+        player::{Dying, Player},
+        tile::{Tile, TILE_SIZE, TILE_Z},
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+};
+
+const KEY_Z: f32 = TILE_Z + 1.;
+const KEY_COLLIDER_SIZE: Vec2 = Vec2::new(TILE_SIZE.x * 2. / 3., TILE_SIZE.y / 3.);
+const KEY_CARRY_OFFSET: Vec3 = Vec3::new(0., TILE_SIZE.y * 2. / 3., 1.);
+const KEY_CARRY_SCALE: f32 = 0.5;
+
+#[derive(Component)]
+pub struct Key;
+
+#[derive(Event)]
+pub struct KeySpawnEvent {
+    pub pos: Vec2,
+    pub tex_idx: usize,
+}
+
+fn on_key_spawn(
+    mut key_spawn_evr: EventReader<KeySpawnEvent>,
+    mut cmds: Commands,
+    tile_assets: Res<TextureAtlasOwner<Tile>>,
+) {
+    for &KeySpawnEvent { pos, tex_idx } in key_spawn_evr.read() {
+        cmds.spawn((
+            Key,
+            StateScoped(GameState::Playing),
+            SpriteBundle {
+                transform: Transform::from_translation(pos.extend(KEY_Z)),
+                texture: tile_assets.texture(),
+                ..default()
+            },
+            TextureAtlas {
+                layout: tile_assets.layout(),
+                index: tex_idx,
+            },
+            Collider::cuboid(KEY_COLLIDER_SIZE.x / 2., KEY_COLLIDER_SIZE.y / 2.),
+            Sensor,
+        ));
+    }
+}
+
+fn pick_up_keys(
+    key_qry: Query<Entity, (With<Key>, With<Sensor>)>,
+    player_qry: Query<Entity, (With<Player>, Without<Dying>)>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+) {
+    for player_id in &player_qry {
+        for key_id in &key_qry {
+            if rapier_ctx.intersection_pair(player_id, key_id) == Some(true) {
+                // The key now lives and dies with its carrier.
+                cmds.entity(key_id)
+                    .remove::<(Collider, Sensor, StateScoped<GameState>)>()
+                    .insert(
+                        Transform::from_translation(KEY_CARRY_OFFSET)
+                            .with_scale(Vec3::splat(KEY_CARRY_SCALE)),
+                    )
+                    .set_parent(player_id);
+            }
+        }
+    }
+}
+
+/// Keys go back up for grabs when their carrier goes down.
+fn drop_keys(
+    key_qry: Query<(Entity, &Parent), (With<Key>, Without<Sensor>)>,
+    carrier_qry: Query<&Transform, With<Dying>>,
+    mut cmds: Commands,
+) {
+    for (key_id, carrier) in &key_qry {
+        let Ok(carrier_xform) = carrier_qry.get(carrier.get()) else {
+            continue;
+        };
+        cmds.entity(key_id).remove_parent().insert((
+            StateScoped(GameState::Playing),
+            Transform::from_translation(carrier_xform.translation.truncate().extend(KEY_Z)),
+            Collider::cuboid(KEY_COLLIDER_SIZE.x / 2., KEY_COLLIDER_SIZE.y / 2.),
+            Sensor,
+        ));
+    }
+}
+
+fn deliver_keys(
+    mut door_qry: Query<(Entity, &Locked, &mut TextureAtlas), With<Door>>,
+    key_qry: Query<(Entity, &Parent), (With<Key>, Without<Sensor>)>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+) {
+    for (key_id, carrier) in &key_qry {
+        for (door_id, lock, mut door_tex_atlas) in &mut door_qry {
+            if rapier_ctx.intersection_pair(carrier.get(), door_id) == Some(true) {
+                door_tex_atlas.index = lock.unlocked_tex_idx;
+                cmds.entity(door_id).remove::<Locked>();
+                cmds.entity(key_id).despawn_recursive();
+                break;
+            }
+        }
+    }
+}
+
+pub fn key_plugin(app: &mut App) {
+    app.add_event::<KeySpawnEvent>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            on_key_spawn.after(level::signal_level_object_spawns),
+        )
+        .add_systems(
+            FixedUpdate,
+            (pick_up_keys, drop_keys, deliver_keys)
+                .chain()
+                .after(PhysicsSet::Writeback)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
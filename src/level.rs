@@ -1,16 +1,20 @@
 use {
     super::{
-        door::DoorSpawnEvent,
+        door::{Door, DoorSpawnEvent},
+        item::{ItemKind, ItemSpawnEvent},
+        key::KeySpawnEvent,
         player::PlayerSpawnEvent,
+        projectile::{ProjectileKind, ThrowableSpawnEvent},
         spike::SpikeSpawnEvent,
         tile::{TileSpawnEvent, TILE_SIZE},
     },
     crate::GameState,
-    bevy::prelude::*,
+    bevy::{ecs::schedule::SystemConfigs, prelude::*},
     bitflags::bitflags,
-    rand::Rng,
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    serde::{Deserialize, Serialize},
     static_assertions::const_assert,
-    std::cmp::Ordering,
+    std::{cmp::Ordering, fmt},
 };
 
 const SECTOR_COLS: usize = 4;
@@ -21,7 +25,14 @@ pub const LEVEL_SIZE: Vec2 = Vec2::new(
     SECTOR_SIZE.y * SECTOR_ROWS as f32,
 );
 
-const_assert!(SECTOR_COLS >= 1 && SECTOR_ROWS >= 2);
+/// Bumped whenever the same seed starts generating different levels, which replays and
+/// ghosts recorded on older levels cannot be played back on.
+pub const GENERATOR_VERSION: u32 = 1;
+
+/// The tallest drop, in tiles, that the critical path may require.
+pub const MAX_REQUIRED_DROP: f32 = SECTOR_SIZE.y;
+
+const_assert!(SECTOR_COLS >= 2 && SECTOR_ROWS >= 2);
 const_assert!(SECTOR_SIZE.x >= 4. && SECTOR_SIZE.y >= 4. && SECTOR_SIZE.y <= 9.);
 
 #[derive(Default, Clone, Copy, PartialEq, Eq)]
@@ -33,6 +44,12 @@ pub enum LevelObject {
     Stalagmite,
     Tile,
     Exit,
+    LockedExit,
+    SecretExit,
+    Key,
+    Item,
+    Throwable,
+    BreakableTile,
     Path,
 }
 
@@ -44,57 +61,159 @@ type LevelLayout =
 bitflags! {
     #[rustfmt::skip]
     #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-    pub struct SectorType: u8 {
-        const ENTRANCE   = 0b00100000;
-        const EXIT       = 0b00010000;
-        const OPEN_UP    = 0b00001000;
-        const OPEN_DOWN  = 0b00000100;
-        const OPEN_LEFT  = 0b00000010;
-        const OPEN_RIGHT = 0b00000001;
-        const CLOSED     = 0b00000000;
+    pub struct SectorType: u16 {
+        /// Set on side rooms carved off the sector to their left.
+        const FROM_LEFT  = 0b10000000000;
+        const ITEM       = 0b01000000000;
+        const SECRET     = 0b00100000000;
+        const KEY        = 0b00010000000;
+        const LOCKED     = 0b00001000000;
+        const ENTRANCE   = 0b00000100000;
+        const EXIT       = 0b00000010000;
+        const OPEN_UP    = 0b00000001000;
+        const OPEN_DOWN  = 0b00000000100;
+        const OPEN_LEFT  = 0b00000000010;
+        const OPEN_RIGHT = 0b00000000001;
+        const CLOSED     = 0b00000000000;
     }
 }
 
-#[derive(Resource)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Destination {
+    NextLevel,
+    BonusLevel,
+    SkipWorld,
+}
+
+/// Terrain whose feel is tuned separately in the movement profile.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum Biome {
+    Ground,
+    Ice,
+    Water,
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct LevelInfo {
     world: u8,
     level: u8,
+    is_bonus: bool,
+    destination: Destination,
 }
 
 impl LevelInfo {
-    const DEFAULT: Self = Self { world: 1, level: 0 };
+    pub const DEFAULT: Self = Self {
+        world: 1,
+        level: 1,
+        is_bonus: false,
+        destination: Destination::NextLevel,
+    };
 
     fn update(&mut self) {
-        if self.level == 4 {
-            self.world += 1;
-            self.level = 0;
+        match self.destination {
+            Destination::NextLevel => {
+                if self.level == 4 {
+                    self.world += 1;
+                    self.level = 0;
+                }
+                self.level += 1;
+                self.is_bonus = false;
+            }
+            Destination::BonusLevel => self.is_bonus = true,
+            Destination::SkipWorld => {
+                self.world += 1;
+                self.level = 1;
+                self.is_bonus = false;
+            }
         }
-        self.level += 1;
+        self.destination = Destination::NextLevel;
     }
 
-    pub fn world(&self) -> u8 {
-        self.world
+    pub fn set_destination(&mut self, destination: Destination) {
+        self.destination = destination;
     }
 
-    pub fn level(&self) -> u8 {
-        self.level
+    pub fn is_bonus(&self) -> bool {
+        self.is_bonus
+    }
+
+    pub fn biome(&self) -> Biome {
+        match self.world {
+            2 => Biome::Ice,
+            3 => Biome::Water,
+            _ => Biome::Ground,
+        }
     }
 }
 
-fn generate_sector_layout() -> SectorLayout {
+impl fmt::Display for LevelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.world, self.level)?;
+        if self.is_bonus {
+            write!(f, " Bonus")?;
+        }
+        Ok(())
+    }
+}
+
+/// Seed every random choice of a run is derived from, so that runs can be replayed.
+#[derive(Resource, Clone, Copy)]
+pub struct RunSeed(pub u64);
+
+#[derive(Resource)]
+struct LevelRng(StdRng);
+
+/// Generated ahead of entering the level, while the title card is up.
+#[derive(Resource)]
+struct NextLevelLayout(LevelLayout);
+
+/// Seeded anew for every level, so that a run continued from a save builds the same levels.
+fn seed_level_rng(
+    run_seed: Res<RunSeed>,
+    level_info: Res<LevelInfo>,
+    mut level_rng: ResMut<LevelRng>,
+) {
+    let level_key = u64::from_le_bytes([
+        level_info.world,
+        level_info.level,
+        level_info.is_bonus as u8,
+        0,
+        0,
+        0,
+        0,
+        0,
+    ]);
+    level_rng.0 = StdRng::seed_from_u64(run_seed.0 ^ level_key);
+}
+
+pub fn advance_level(mut level_info: ResMut<LevelInfo>) {
+    level_info.update();
+}
+
+fn generate_sector_layout(
+    level_info: Res<LevelInfo>,
+    mut level_rng: ResMut<LevelRng>,
+) -> SectorLayout {
+    let rng = &mut level_rng.0;
     let mut sector_layout = [[SectorType::CLOSED; SECTOR_COLS]; SECTOR_ROWS];
 
-    let entrance_pos = rand::thread_rng().gen_range(0..SECTOR_COLS);
+    let entrance_pos = rng.gen_range(0..SECTOR_COLS);
     sector_layout[0][entrance_pos] |= SectorType::ENTRANCE;
 
-    let exit_pos = rand::thread_rng().gen_range(0..SECTOR_COLS);
+    let exit_pos = rng.gen_range(0..SECTOR_COLS);
     sector_layout[SECTOR_ROWS - 1][exit_pos] |= SectorType::EXIT;
 
     let mut down_sectors = [0; SECTOR_ROWS];
     let mut up_sectors = [0; SECTOR_ROWS];
 
     for y in 0..SECTOR_ROWS - 1 {
-        down_sectors[y] = rand::thread_rng().gen_range(0..SECTOR_COLS);
+        down_sectors[y] = if y == 0 {
+            rng.gen_range(0..SECTOR_COLS)
+        } else {
+            // Never directly below the shaft above, so drops cannot chain into deeper ones.
+            let x = rng.gen_range(0..SECTOR_COLS - 1);
+            x + (x >= up_sectors[y]) as usize
+        };
         sector_layout[y][down_sectors[y]] |= SectorType::OPEN_DOWN;
 
         up_sectors[y + 1] = down_sectors[y];
@@ -126,10 +245,57 @@ fn generate_sector_layout() -> SectorLayout {
             sector_layout[y][x] |= SectorType::OPEN_LEFT | SectorType::OPEN_RIGHT;
         }
     }
+
+    if rng.gen_ratio(1, 2) && carve_side_room(&mut sector_layout, SectorType::KEY, rng) {
+        sector_layout[SECTOR_ROWS - 1][exit_pos] |= SectorType::LOCKED;
+    }
+    if !level_info.is_bonus() && rng.gen_ratio(1, 3) {
+        carve_side_room(&mut sector_layout, SectorType::SECRET, rng);
+    }
+    if level_info.is_bonus() || rng.gen_ratio(1, 2) {
+        carve_side_room(&mut sector_layout, SectorType::ITEM, rng);
+    }
     sector_layout
 }
 
-fn generate_level_layout(In(sector_layout): In<SectorLayout>) -> LevelLayout {
+/// Opens a closed sector next to the critical path and tags it with `room_type`.
+/// Returns `false` if every sector adjacent to the path is already in use.
+fn carve_side_room(
+    sector_layout: &mut SectorLayout,
+    room_type: SectorType,
+    rng: &mut impl Rng,
+) -> bool {
+    let side_rooms = (0..SECTOR_ROWS)
+        .flat_map(|y| (0..SECTOR_COLS).map(move |x| (y, x)))
+        .filter(|&(y, x)| {
+            sector_layout[y][x] == SectorType::CLOSED
+                && ((x > 0 && sector_layout[y][x - 1] != SectorType::CLOSED)
+                    || (x < SECTOR_COLS - 1 && sector_layout[y][x + 1] != SectorType::CLOSED))
+        })
+        .collect::<Vec<_>>();
+
+    if side_rooms.is_empty() {
+        return false;
+    }
+    let (y, x) = side_rooms[rng.gen_range(0..side_rooms.len())];
+    sector_layout[y][x] |= room_type;
+
+    if x > 0 && sector_layout[y][x - 1] != SectorType::CLOSED {
+        sector_layout[y][x - 1] |= SectorType::OPEN_RIGHT;
+        sector_layout[y][x] |= SectorType::OPEN_LEFT | SectorType::FROM_LEFT;
+    } else {
+        sector_layout[y][x + 1] |= SectorType::OPEN_LEFT;
+        sector_layout[y][x] |= SectorType::OPEN_RIGHT;
+    }
+    true
+}
+
+fn generate_level_layout(
+    In(sector_layout): In<SectorLayout>,
+    level_info: Res<LevelInfo>,
+    mut level_rng: ResMut<LevelRng>,
+) -> LevelLayout {
+    let rng = &mut level_rng.0;
     let mut level_layout = LevelLayout::default();
 
     for r in 0..SECTOR_ROWS {
@@ -150,6 +316,13 @@ fn generate_level_layout(In(sector_layout): In<SectorLayout>) -> LevelLayout {
                     sector_contents[i][SECTOR_SIZE.x as usize / 2] = LevelObject::Path;
                 }
             }
+            if sector_type.intersects(SectorType::OPEN_UP)
+                && !sector_type.intersects(SectorType::OPEN_DOWN)
+            {
+                // Caps the drop from the sector above at `MAX_REQUIRED_DROP`.
+                sector_contents[SECTOR_SIZE.y as usize / 2 + 1][SECTOR_SIZE.x as usize / 2] =
+                    LevelObject::Tile;
+            }
             if sector_type.intersects(SectorType::OPEN_DOWN) {
                 for i in SECTOR_SIZE.y as usize / 2..SECTOR_SIZE.y as usize {
                     sector_contents[i][SECTOR_SIZE.x as usize / 2] = LevelObject::Path;
@@ -168,9 +341,28 @@ fn generate_level_layout(In(sector_layout): In<SectorLayout>) -> LevelLayout {
             if sector_type.intersects(SectorType::ENTRANCE) {
                 sector_contents[SECTOR_SIZE.y as usize / 2][SECTOR_SIZE.x as usize / 2] =
                     LevelObject::Entrance;
+            } else if sector_type.contains(SectorType::EXIT | SectorType::LOCKED) {
+                sector_contents[SECTOR_SIZE.y as usize / 2][SECTOR_SIZE.x as usize / 2] =
+                    LevelObject::LockedExit;
             } else if sector_type.intersects(SectorType::EXIT) {
                 sector_contents[SECTOR_SIZE.y as usize / 2][SECTOR_SIZE.x as usize / 2] =
                     LevelObject::Exit;
+            } else if sector_type.intersects(SectorType::KEY) {
+                sector_contents[SECTOR_SIZE.y as usize / 2][SECTOR_SIZE.x as usize / 2] =
+                    LevelObject::Key;
+            } else if sector_type.intersects(SectorType::ITEM) {
+                sector_contents[SECTOR_SIZE.y as usize / 2][SECTOR_SIZE.x as usize / 2] =
+                    LevelObject::Item;
+            } else if sector_type.intersects(SectorType::SECRET) {
+                sector_contents[SECTOR_SIZE.y as usize / 2][SECTOR_SIZE.x as usize / 2] =
+                    LevelObject::SecretExit;
+                // Later side rooms may open the other side, which must stay behind the wall.
+                let wall_x = if sector_type.intersects(SectorType::FROM_LEFT) {
+                    0
+                } else {
+                    SECTOR_SIZE.x as usize - 1
+                };
+                sector_contents[SECTOR_SIZE.y as usize / 2][wall_x] = LevelObject::BreakableTile;
             }
 
             for y in (1..SECTOR_SIZE.y as usize / 2)
@@ -190,7 +382,7 @@ fn generate_level_layout(In(sector_layout): In<SectorLayout>) -> LevelLayout {
                         ]
                         .into_iter()
                         .any(|neighbor| neighbor == LevelObject::Tile)
-                        && rand::thread_rng().gen_ratio(1, 3)
+                        && rng.gen_ratio(1, 3)
                     {
                         sector_contents[y][x] = LevelObject::Tile;
                     }
@@ -198,8 +390,9 @@ fn generate_level_layout(In(sector_layout): In<SectorLayout>) -> LevelLayout {
             }
             for y in 1..SECTOR_SIZE.y as usize - 1 {
                 for x in 0..SECTOR_SIZE.x as usize {
-                    if sector_contents[y][x] == LevelObject::Background
-                        && rand::thread_rng().gen_ratio(1, 4)
+                    if !level_info.is_bonus()
+                        && sector_contents[y][x] == LevelObject::Background
+                        && rng.gen_ratio(1, 4)
                     {
                         if sector_contents[y - 1][x] == LevelObject::Tile
                             && sector_contents[y + 1][x] == LevelObject::Background
@@ -213,19 +406,37 @@ fn generate_level_layout(In(sector_layout): In<SectorLayout>) -> LevelLayout {
                     }
                 }
             }
+            for y in 1..SECTOR_SIZE.y as usize - 1 {
+                for x in 1..SECTOR_SIZE.x as usize - 1 {
+                    if sector_contents[y][x] == LevelObject::Background
+                        && sector_contents[y + 1][x] == LevelObject::Tile
+                        && rng.gen_ratio(1, 16)
+                    {
+                        sector_contents[y][x] = LevelObject::Throwable;
+                    }
+                }
+            }
             level_layout[r][c] = sector_contents;
         }
     }
     level_layout
 }
 
+fn store_next_level_layout(In(level_layout): In<LevelLayout>, mut cmds: Commands) {
+    cmds.insert_resource(NextLevelLayout(level_layout));
+}
+
 pub fn signal_level_object_spawns(
-    In(level_layout): In<LevelLayout>,
+    next_level_layout: Res<NextLevelLayout>,
     level_info: Res<LevelInfo>,
+    mut level_rng: ResMut<LevelRng>,
     mut tile_spawn_evw: EventWriter<TileSpawnEvent>,
     mut player_spawn_evw: EventWriter<PlayerSpawnEvent>,
     mut spike_spawn_evw: EventWriter<SpikeSpawnEvent>,
     mut door_spawn_evw: EventWriter<DoorSpawnEvent>,
+    mut key_spawn_evw: EventWriter<KeySpawnEvent>,
+    mut item_spawn_evw: EventWriter<ItemSpawnEvent>,
+    mut throwable_spawn_evw: EventWriter<ThrowableSpawnEvent>,
 ) {
     for r in 0..SECTOR_ROWS {
         for c in 0..SECTOR_COLS {
@@ -244,11 +455,19 @@ pub fn signal_level_object_spawns(
                     .translation
                     .truncate();
 
-                    match level_layout[r][c][y][x] {
+                    match next_level_layout.0[r][c][y][x] {
                         LevelObject::Tile => {
                             tile_spawn_evw.send(TileSpawnEvent {
                                 pos,
                                 tex_idx: 5 + level_info.world as usize,
+                                is_breakable: false,
+                            });
+                        }
+                        LevelObject::BreakableTile => {
+                            tile_spawn_evw.send(TileSpawnEvent {
+                                pos,
+                                tex_idx: 5 + level_info.world as usize,
+                                is_breakable: true,
                             });
                         }
                         LevelObject::Entrance => {
@@ -256,14 +475,55 @@ pub fn signal_level_object_spawns(
                             door_spawn_evw.send(DoorSpawnEvent {
                                 pos,
                                 tex_idx: 75 + level_info.world as usize,
-                                is_exit: false,
+                                door: Door::Entrance,
+                                locked_tex_idx: None,
                             });
                         }
                         LevelObject::Exit => {
                             door_spawn_evw.send(DoorSpawnEvent {
                                 pos,
                                 tex_idx: 75,
-                                is_exit: true,
+                                door: Door::Exit(Destination::NextLevel),
+                                locked_tex_idx: None,
+                            });
+                        }
+                        LevelObject::LockedExit => {
+                            door_spawn_evw.send(DoorSpawnEvent {
+                                pos,
+                                tex_idx: 75,
+                                door: Door::Exit(Destination::NextLevel),
+                                locked_tex_idx: Some(76 + level_info.world as usize % 4),
+                            });
+                        }
+                        LevelObject::SecretExit => {
+                            door_spawn_evw.send(DoorSpawnEvent {
+                                pos,
+                                tex_idx: 75,
+                                door: Door::Exit(if level_rng.0.gen_ratio(1, 4) {
+                                    Destination::SkipWorld
+                                } else {
+                                    Destination::BonusLevel
+                                }),
+                                locked_tex_idx: None,
+                            });
+                        }
+                        LevelObject::Key => {
+                            key_spawn_evw.send(KeySpawnEvent {
+                                pos,
+                                tex_idx: 63 + level_info.world as usize % 4,
+                            });
+                        }
+                        LevelObject::Item => {
+                            item_spawn_evw.send(ItemSpawnEvent {
+                                pos,
+                                kind: ItemKind::ALL[level_rng.0.gen_range(0..ItemKind::ALL.len())],
+                            });
+                        }
+                        LevelObject::Throwable => {
+                            throwable_spawn_evw.send(ThrowableSpawnEvent {
+                                pos,
+                                kind: ProjectileKind::PICKUPS
+                                    [level_rng.0.gen_range(0..ProjectileKind::PICKUPS.len())],
                             });
                         }
                         spike_type @ (LevelObject::Stalactite | LevelObject::Stalagmite) => {
@@ -280,15 +540,33 @@ pub fn signal_level_object_spawns(
     }
 }
 
+fn generate_next_level() -> SystemConfigs {
+    (
+        seed_level_rng,
+        generate_sector_layout
+            .pipe(generate_level_layout)
+            .pipe(store_next_level_layout),
+    )
+        .chain()
+}
+
 pub fn level_plugin(app: &mut App) {
-    app.insert_resource(LevelInfo::DEFAULT).add_systems(
-        OnEnter(GameState::Playing),
-        (
-            |mut level_info: ResMut<LevelInfo>| level_info.update(),
-            generate_sector_layout
-                .pipe(generate_level_layout)
-                .pipe(signal_level_object_spawns),
+    let run_seed = RunSeed(rand::random());
+
+    app.insert_resource(LevelInfo::DEFAULT)
+        .insert_resource(run_seed)
+        .insert_resource(LevelRng(StdRng::seed_from_u64(run_seed.0)))
+        .add_systems(OnExit(GameState::CharacterSelect), generate_next_level())
+        .add_systems(
+            OnEnter(GameState::Transition),
+            (advance_level, generate_next_level()).chain(),
         )
-            .chain(),
-    );
+        .add_systems(OnEnter(GameState::Playing), signal_level_object_spawns)
+        .add_systems(
+            OnExit(GameState::GameOver),
+            |mut level_info: ResMut<LevelInfo>, mut run_seed: ResMut<RunSeed>| {
+                *level_info = LevelInfo::DEFAULT;
+                *run_seed = RunSeed(rand::random());
+            },
+        );
 }
@@ -1,13 +1,30 @@
 mod animation;
 mod asset_owner;
+mod character;
 mod combat;
+mod controls;
 mod door;
+mod fall_damage;
+mod game_over;
+mod ghost;
+mod interaction;
+mod item;
+mod key;
 mod level;
 mod main_camera;
+mod melee;
 mod mouse_position;
+mod movement;
 mod player;
+mod projectile;
+mod replay;
+mod run_state;
 mod spike;
+mod stats;
+#[cfg(test)]
+mod testing;
 mod tile;
+mod transition;
 mod ui;
 mod sprite_flip;
 
@@ -21,7 +38,9 @@ use {
     bevy_rapier2d::prelude::*,
     bevy_tnua::prelude::*,
     bevy_tnua_rapier2d::TnuaRapier2dPlugin,
+    character::CharacterAtlases,
     leafwing_input_manager::prelude::*,
+    movement::MovementProfileOwner,
     player::PlayerAction,
     static_assertions::const_assert,
     tile::TILE_SIZE,
@@ -35,8 +54,10 @@ const_assert!(RESOLUTION.x / RESOLUTION.y == 16. / 9.);
 enum GameState {
     #[default]
     Setup,
+    CharacterSelect,
     Playing,
     Transition,
+    GameOver,
 }
 
 fn main() {
@@ -79,12 +100,29 @@ fn main() {
                 animation::animation_plugin,
                 sprite_flip::sprite_flip_plugin,
                 ui::ui_plugin,
+                controls::controls_plugin,
+                transition::transition_plugin,
+                game_over::game_over_plugin,
+                replay::replay_plugin,
+                ghost::ghost_plugin,
+                character::character_plugin,
+            ),
+            (
                 combat::combat_plugin,
                 level::level_plugin,
                 player::player_plugin,
                 tile::tile_plugin,
                 door::door_plugin,
+                key::key_plugin,
+                interaction::interaction_plugin,
                 spike::spike_plugin,
+                melee::melee_plugin,
+                projectile::projectile_plugin,
+                item::item_plugin,
+                movement::movement_plugin,
+                fall_damage::fall_damage_plugin,
+                run_state::run_state_plugin,
+                stats::stats_plugin,
             ),
         ))
         .init_state::<GameState>()
@@ -97,17 +135,18 @@ fn main() {
         )
         .add_systems(
             Update,
-            (|mut next_state: ResMut<NextState<GameState>>| {
-                next_state.set(GameState::Playing);
+            // Replays check the movement profile on their first tick.
+            (|asset_server: Res<AssetServer>,
+              profile_owner: Res<MovementProfileOwner>,
+              character_atlases: Res<CharacterAtlases>,
+              mut next_state: ResMut<NextState<GameState>>| {
+                if profile_owner.has_finished_loading(&asset_server)
+                    && character_atlases.have_finished_loading(&asset_server)
+                {
+                    next_state.set(GameState::CharacterSelect);
+                }
             })
             .run_if(in_state(GameState::Setup)),
         )
-        .add_systems(
-            OnTransition {
-                entered: GameState::Transition,
-                exited: GameState::Playing,
-            },
-            |mut next_state: ResMut<NextState<GameState>>| next_state.set(GameState::Playing),
-        )
         .run();
 }
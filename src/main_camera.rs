@@ -4,20 +4,48 @@ use {
     bevy::prelude::*,
 };
 
+/// Room kept around the players when the camera zooms out to frame them all.
+const CAMERA_FRAME_MARGIN: f32 = 2. * TILE_SIZE.x;
+
 #[derive(Component)]
 pub struct MainCamera;
 
-fn follow_player(
-    mut cam_qry: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
+/// The zoom picked with the zoom keys, widened as needed to keep every player in view.
+#[derive(Component)]
+struct CameraZoom(f32);
+
+fn follow_players(
+    mut cam_qry: Query<
+        (
+            &Camera,
+            &CameraZoom,
+            &mut OrthographicProjection,
+            &mut Transform,
+        ),
+        (With<MainCamera>, Without<Player>),
+    >,
     player_qry: Query<&Transform, (With<Player>, Without<MainCamera>)>,
 ) {
-    let mut cam_xform = cam_qry.single_mut();
-    let player_xform = player_qry.single();
+    let (cam, cam_zoom, mut cam_proj, mut cam_xform) = cam_qry.single_mut();
+    let Some((players_min, players_max)) = player_qry
+        .iter()
+        .map(|player_xform| player_xform.translation.truncate())
+        .fold(None, |bounds, pos| {
+            Some(bounds.map_or((pos, pos), |(min, max): (Vec2, Vec2)| {
+                (min.min(pos), max.max(pos))
+            }))
+        })
+    else {
+        return;
+    };
 
-    cam_xform.translation = player_xform
-        .translation
-        .truncate()
-        .extend(cam_xform.translation.z);
+    cam_xform.translation = ((players_min + players_max) / 2.).extend(cam_xform.translation.z);
+
+    if let Some(viewport_size) = cam.logical_viewport_size() {
+        let framing_scale =
+            ((players_max - players_min + 2. * CAMERA_FRAME_MARGIN) / viewport_size).max_element();
+        cam_proj.scale = cam_zoom.0.max(framing_scale);
+    }
 }
 
 fn clamp_camera_to_tilemap(
@@ -55,25 +83,25 @@ fn clamp_camera_to_tilemap(
 
 // temporary
 fn adjust_camera_zoom(
-    mut cam_qry: Query<&mut OrthographicProjection, With<MainCamera>>,
+    mut cam_qry: Query<&mut CameraZoom, With<MainCamera>>,
     kb: Res<ButtonInput<KeyCode>>,
     time: Res<Time>,
 ) {
     let dt = time.delta_seconds();
-    let mut cam_proj = cam_qry.single_mut();
+    let mut cam_zoom = cam_qry.single_mut();
 
     if kb.pressed(KeyCode::NumpadSubtract) {
-        cam_proj.scale += dt;
+        cam_zoom.0 += dt;
     }
     if kb.pressed(KeyCode::NumpadAdd) {
-        cam_proj.scale -= dt;
+        cam_zoom.0 -= dt;
     }
 }
 
 pub fn main_camera_plugin(app: &mut App) {
     app.insert_resource(ClearColor(Color::srgb_u8(208, 187, 148)))
         .add_systems(OnEnter(GameState::Setup), |mut cmds: Commands| {
-            cmds.spawn((MainCamera, Camera2dBundle::default()));
+            cmds.spawn((MainCamera, CameraZoom(1.), Camera2dBundle::default()));
         })
         .add_systems(
             Update,
@@ -83,6 +111,6 @@ pub fn main_camera_plugin(app: &mut App) {
         )
         .add_systems(
             FixedPostUpdate,
-            follow_player.run_if(in_state(GameState::Playing)),
+            follow_players.run_if(in_state(GameState::Playing)),
         );
 }
@@ -0,0 +1,183 @@
+use {
+    super::{
+        combat::{self, Damage, DamageOwner, Health},
+        player::{DoorTransit, Dying, Player, PlayerAction},
+        sprite_flip::Flippable,
+        tile::TILE_SIZE,
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    leafwing_input_manager::prelude::*,
+    std::time::Duration,
+};
+
+const ATTACK_DURATION: Duration = Duration::from_millis(200);
+const ATTACK_COOLDOWN: Duration = Duration::from_millis(400);
+const ATTACK_DAMAGE: Damage = Damage::fixed(1, 6.);
+const HITBOX_SIZE: Vec2 = Vec2::new(TILE_SIZE.x / 2., TILE_SIZE.y / 2.);
+const HITBOX_OFFSET: f32 = TILE_SIZE.x / 3.;
+const HITSTOP_DURATION: Duration = Duration::from_millis(80);
+const HITSTOP_SPEED: f32 = 0.05;
+
+#[derive(Component)]
+pub struct MeleeAttacker {
+    cooldown: Timer,
+}
+
+impl Default for MeleeAttacker {
+    fn default() -> Self {
+        Self {
+            cooldown: Timer::new(Duration::ZERO, TimerMode::Once),
+        }
+    }
+}
+
+/// Present on an attacker for as long as its hitbox is out.
+#[derive(Component)]
+pub struct Attacking {
+    timer: Timer,
+    hitbox: Entity,
+}
+
+#[derive(Component)]
+struct MeleeHitbox {
+    is_hit_confirmed: bool,
+}
+
+/// Briefly slows the game down to sell a landed hit.
+#[derive(Resource)]
+struct Hitstop(Timer);
+
+fn start_attacks(
+    mut attacker_qry: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &mut MeleeAttacker,
+            &Flippable,
+        ),
+        (
+            With<Player>,
+            Without<Attacking>,
+            Without<DoorTransit>,
+            Without<Dying>,
+        ),
+    >,
+    time: Res<Time>,
+    mut cmds: Commands,
+) {
+    for (attacker_id, attacker_in, mut attacker, attacker_flippable) in &mut attacker_qry {
+        attacker.cooldown.tick(time.delta());
+        if !attacker.cooldown.finished() || !attacker_in.just_pressed(&PlayerAction::Attack) {
+            continue;
+        }
+        attacker.cooldown = Timer::new(ATTACK_COOLDOWN, TimerMode::Once);
+
+        // Spawned already facing the right way, as flips only propagate in `Update`.
+        let hitbox_id = cmds
+            .spawn((
+                MeleeHitbox {
+                    is_hit_confirmed: false,
+                },
+                Flippable {
+                    flip_x: attacker_flippable.flip_x,
+                    ..default()
+                },
+                SpatialBundle::from_transform(Transform::from_xyz(
+                    if attacker_flippable.flip_x {
+                        -HITBOX_OFFSET
+                    } else {
+                        HITBOX_OFFSET
+                    },
+                    0.,
+                    0.,
+                )),
+                Collider::cuboid(HITBOX_SIZE.x / 2., HITBOX_SIZE.y / 2.),
+                ColliderMassProperties::Density(0.),
+                Sensor,
+                ATTACK_DAMAGE,
+                DamageOwner(attacker_id),
+                Name::new("Melee"),
+            ))
+            .set_parent(attacker_id)
+            .id();
+        cmds.entity(attacker_id).insert(Attacking {
+            timer: Timer::new(ATTACK_DURATION, TimerMode::Once),
+            hitbox: hitbox_id,
+        });
+    }
+}
+
+fn end_attacks(
+    mut attacker_qry: Query<(Entity, &mut Attacking)>,
+    time: Res<Time>,
+    mut cmds: Commands,
+) {
+    for (attacker_id, mut attacking) in &mut attacker_qry {
+        if attacking.timer.tick(time.delta()).finished() {
+            cmds.entity(attacking.hitbox).despawn_recursive();
+            cmds.entity(attacker_id).remove::<Attacking>();
+        }
+    }
+}
+
+fn confirm_hits(
+    mut hitbox_qry: Query<(Entity, &mut MeleeHitbox, &Parent)>,
+    hp_qry: Query<(), With<Health>>,
+    rapier_ctx: Res<RapierContext>,
+    mut time: ResMut<Time<Virtual>>,
+    mut cmds: Commands,
+) {
+    for (hitbox_id, mut hitbox, attacker) in &mut hitbox_qry {
+        if hitbox.is_hit_confirmed {
+            continue;
+        }
+        hitbox.is_hit_confirmed = rapier_ctx
+            .intersection_pairs_with(hitbox_id)
+            .filter(|&(_, _, is_intersecting)| is_intersecting)
+            .map(|(a, b, _)| if a == hitbox_id { b } else { a })
+            .any(|target_id| target_id != attacker.get() && hp_qry.contains(target_id));
+
+        if hitbox.is_hit_confirmed {
+            time.set_relative_speed(HITSTOP_SPEED);
+            cmds.insert_resource(Hitstop(Timer::new(HITSTOP_DURATION, TimerMode::Once)));
+        }
+    }
+}
+
+fn update_hitstop(
+    real_time: Res<Time<Real>>,
+    hitstop: Option<ResMut<Hitstop>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut cmds: Commands,
+) {
+    let Some(mut hitstop) = hitstop else {
+        return;
+    };
+    if hitstop.0.tick(real_time.delta()).finished() {
+        time.set_relative_speed(1.);
+        cmds.remove_resource::<Hitstop>();
+    }
+}
+
+pub fn melee_plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        (
+            (start_attacks, end_attacks)
+                .chain()
+                .before(PhysicsSet::SyncBackend),
+            confirm_hits.after(combat::deal_damage),
+        )
+            .run_if(in_state(GameState::Playing)),
+    )
+    .add_systems(Update, update_hitstop)
+    .add_systems(
+        OnExit(GameState::Playing),
+        |mut time: ResMut<Time<Virtual>>, mut cmds: Commands| {
+            time.set_relative_speed(1.);
+            cmds.remove_resource::<Hitstop>();
+        },
+    );
+}
@@ -0,0 +1,202 @@
+use {
+    super::{
+        asset_owner,
+        character::Character,
+        level::{Biome, LevelInfo},
+        player::Player,
+    },
+    crate::GameState,
+    bevy::{
+        asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+        prelude::*,
+        utils::HashMap,
+    },
+    bevy_tnua::prelude::*,
+    serde::Deserialize,
+    std::{
+        error::Error,
+        hash::{DefaultHasher, Hash, Hasher},
+    },
+};
+
+const MOVEMENT_PROFILE_PATH: &str = "player.movement.ron";
+
+/// How the player moves, with distances in tiles, durations in seconds and angles in degrees.
+#[derive(Component, Deserialize, Clone, Copy)]
+pub struct MovementTuning {
+    pub run_speed: f32,
+    pub acceleration: f32,
+    pub air_acceleration: f32,
+    pub float_height: f32,
+    pub spring_dampening: f32,
+    pub jump_height: f32,
+    pub max_slope: f32,
+    pub air_jumps: usize,
+    /// How long after walking off a ledge a jump still counts as a ground jump.
+    pub coyote_time: f32,
+    /// How long before landing a jump press is remembered.
+    pub jump_buffer_time: f32,
+}
+
+/// Used until the profile has loaded. Matches the shipped profile's base tuning.
+impl Default for MovementTuning {
+    fn default() -> Self {
+        Self {
+            run_speed: 4.,
+            acceleration: 5.,
+            air_acceleration: 5.,
+            float_height: 0.359375,
+            spring_dampening: 0.5,
+            jump_height: 1.5,
+            max_slope: 45.,
+            air_jumps: 1,
+            coyote_time: 0.15,
+            jump_buffer_time: 0.2,
+        }
+    }
+}
+
+/// Replaces whichever fields of a [`MovementTuning`] are set.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(default)]
+struct MovementOverrides {
+    run_speed: Option<f32>,
+    acceleration: Option<f32>,
+    air_acceleration: Option<f32>,
+    float_height: Option<f32>,
+    spring_dampening: Option<f32>,
+    jump_height: Option<f32>,
+    max_slope: Option<f32>,
+    air_jumps: Option<usize>,
+    coyote_time: Option<f32>,
+    jump_buffer_time: Option<f32>,
+}
+
+impl MovementTuning {
+    fn with(self, overrides: Option<&MovementOverrides>) -> Self {
+        let Some(overrides) = overrides else {
+            return self;
+        };
+        Self {
+            run_speed: overrides.run_speed.unwrap_or(self.run_speed),
+            acceleration: overrides.acceleration.unwrap_or(self.acceleration),
+            air_acceleration: overrides.air_acceleration.unwrap_or(self.air_acceleration),
+            float_height: overrides.float_height.unwrap_or(self.float_height),
+            spring_dampening: overrides.spring_dampening.unwrap_or(self.spring_dampening),
+            jump_height: overrides.jump_height.unwrap_or(self.jump_height),
+            max_slope: overrides.max_slope.unwrap_or(self.max_slope),
+            air_jumps: overrides.air_jumps.unwrap_or(self.air_jumps),
+            coyote_time: overrides.coyote_time.unwrap_or(self.coyote_time),
+            jump_buffer_time: overrides.jump_buffer_time.unwrap_or(self.jump_buffer_time),
+        }
+    }
+}
+
+/// Movement tuning loaded from [`MOVEMENT_PROFILE_PATH`] and hot-reloaded on change.
+/// Biome overrides are applied on top of character overrides.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct MovementProfile {
+    base: MovementTuning,
+    #[serde(default)]
+    characters: HashMap<Character, MovementOverrides>,
+    #[serde(default)]
+    biomes: HashMap<Biome, MovementOverrides>,
+    /// Of the file contents, so that replays can tell which profile they were recorded with.
+    #[serde(skip)]
+    hash: u64,
+}
+
+impl MovementProfile {
+    pub fn tuning(&self, character: Character, biome: Option<Biome>) -> MovementTuning {
+        self.base
+            .with(self.characters.get(&character))
+            .with(biome.and_then(|biome| self.biomes.get(&biome)))
+    }
+}
+
+#[derive(Default)]
+struct MovementProfileLoader;
+
+impl AssetLoader for MovementProfileLoader {
+    type Asset = MovementProfile;
+    type Settings = ();
+    type Error = Box<dyn Error + Send + Sync>;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut profile: MovementProfile = ron::de::from_bytes(&bytes)?;
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        profile.hash = hasher.finish();
+        Ok(profile)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["movement.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct MovementProfileOwner(Handle<MovementProfile>);
+
+impl MovementProfileOwner {
+    /// The tuning for `character` in `biome`, or the defaults while the profile is loading.
+    pub fn tuning(
+        &self,
+        profiles: &Assets<MovementProfile>,
+        character: Character,
+        biome: Option<Biome>,
+    ) -> MovementTuning {
+        profiles
+            .get(&self.0)
+            .map(|profile| profile.tuning(character, biome))
+            .unwrap_or_default()
+    }
+
+    pub fn has_finished_loading(&self, asset_server: &AssetServer) -> bool {
+        asset_owner::has_finished_loading(asset_server, &self.0)
+    }
+
+    /// Identifies the loaded profile, or is 0 while it is loading.
+    pub fn profile_hash(&self, profiles: &Assets<MovementProfile>) -> u64 {
+        profiles.get(&self.0).map_or(0, |profile| profile.hash)
+    }
+}
+
+/// Re-reads the tuning every tick so that edits to the profile apply immediately.
+fn update_movement_tuning(
+    mut player_qry: Query<(&Character, &mut MovementTuning), With<Player>>,
+    profile_owner: Res<MovementProfileOwner>,
+    profiles: Res<Assets<MovementProfile>>,
+    level_info: Res<LevelInfo>,
+) {
+    for (player_character, mut player_tuning) in &mut player_qry {
+        *player_tuning =
+            profile_owner.tuning(&profiles, *player_character, Some(level_info.biome()));
+    }
+}
+
+pub fn movement_plugin(app: &mut App) {
+    app.init_asset::<MovementProfile>()
+        .init_asset_loader::<MovementProfileLoader>()
+        .add_systems(
+            OnEnter(GameState::Setup),
+            |mut cmds: Commands, asset_server: Res<AssetServer>| {
+                cmds.insert_resource(MovementProfileOwner(
+                    asset_server.load(MOVEMENT_PROFILE_PATH),
+                ));
+            },
+        )
+        .add_systems(
+            FixedUpdate,
+            update_movement_tuning
+                .before(TnuaUserControlsSystemSet)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
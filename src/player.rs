@@ -1,15 +1,22 @@
 use {
     super::{
         animation::{self, AnimationIndices, AnimationState, AnimationTimer},
-        asset_owner::TextureAtlasOwner,
-        combat::Health,
-        door::Door,
-        level,
+        character::{Character, CharacterAtlases, SelectedCharacter, SelectedModifiers},
+        combat::{DeathEvent, Health, HitStun, Iframes, KeepOnDeath, LastHitBy, MaxHealth},
+        controls::{Controls, InputDevices},
+        fall_damage::{FallTracker, LandingStun},
+        game_over::GameOverInfo,
+        item::Inventory,
+        level::{self, LevelInfo, RunSeed},
+        melee::{Attacking, MeleeAttacker},
+        movement::MovementTuning,
+        projectile::{ThrowAim, Thrower, CHARACTER_GROUP},
+        run_state::RunState,
         sprite_flip::Flippable,
-        tile::{TILE_SIZE, TILE_Z},
+        tile::{Tile, TILE_SIZE, TILE_Z},
     },
     crate::GameState,
-    bevy::prelude::*,
+    bevy::{prelude::*, sprite::Anchor},
     bevy_rapier2d::prelude::*,
     bevy_tnua::{
         builtins::TnuaBuiltinJumpState,
@@ -19,41 +26,185 @@ use {
     },
     bevy_tnua_rapier2d::{TnuaRapier2dIOBundle, TnuaRapier2dSensorShape},
     leafwing_input_manager::prelude::*,
-    static_assertions::const_assert,
-    std::{f32::consts::FRAC_PI_4, time::Duration},
+    serde::{Deserialize, Serialize},
+    std::time::Duration,
 };
 
 const PLAYER_Z: f32 = TILE_Z + 2.;
 const PLAYER_COLLIDER_HALF_HEIGHT: f32 = 16.;
 const PLAYER_COLLDIER_RADIUS: f32 = 16.;
-pub const PLAYER_MAX_HEALTH: Health = Health(10);
+const PLAYER_FLOAT_HEIGHT: f32 = PLAYER_COLLIDER_HALF_HEIGHT + PLAYER_COLLDIER_RADIUS + 14.;
+pub const PLAYER_SPRITE_SIZE: UVec2 = UVec2::new(80, 110);
 
-const_assert!(PLAYER_MAX_HEALTH.0 > 0 && PLAYER_MAX_HEALTH.0 % 2 == 0);
+pub const DOOR_TRANSIT_DURATION: Duration = Duration::from_millis(750);
+const PLAYER_DEATH_DURATION: Duration = Duration::from_millis(1500);
+
+const CROUCH_FLOAT_HEIGHT: f32 = PLAYER_FLOAT_HEIGHT - PLAYER_COLLIDER_HALF_HEIGHT * 1.5;
+const CROUCH_SPEED_FACTOR: f32 = 0.4;
+
+const WALL_CONTACT_DISTANCE: f32 = 4.;
+const WALL_SLIDE_SPEED: f32 = TILE_SIZE.y;
+const WALL_KICK_DURATION: Duration = Duration::from_millis(250);
+
+const LEDGE_REACH: f32 = PLAYER_COLLIDER_HALF_HEIGHT + PLAYER_COLLDIER_RADIUS + 8.;
+const LEDGE_HANG_DEPTH: f32 = PLAYER_COLLIDER_HALF_HEIGHT * 2.;
+const LEDGE_CLIMB_DURATION: Duration = Duration::from_millis(250);
+const LEDGE_LET_GO_DURATION: Duration = Duration::from_millis(300);
+
+const DASH_DISTANCE: f32 = 2.5 * TILE_SIZE.x;
+const DASH_SPEED: f32 = 12. * TILE_SIZE.x;
+const DASH_COOLDOWN: Duration = Duration::from_millis(600);
+const DASH_IFRAMES_DURATION: Duration = Duration::from_millis(300);
+
+pub const MAX_PLAYERS: usize = 2;
+const PLAYER_SPAWN_SPACING: f32 = TILE_SIZE.x / 4.;
+/// Tells players apart when they play the same character.
+const PLAYER_TINTS: [Color; MAX_PLAYERS] = [Color::WHITE, Color::srgb(0.6, 0.8, 1.)];
+const DOWNED_RESPAWN_HEALTH: Health = Health(2);
+
+/// The player's index, which picks their devices, HUD row and tint.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct Player(pub usize);
+
+/// How many players take part in a run, chosen on the character select screen.
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+pub struct PlayerCount(pub usize);
+
+impl Default for PlayerCount {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+pub fn is_single_player(player_count: Res<PlayerCount>) -> bool {
+    player_count.0 == 1
+}
 
 #[derive(Component)]
-pub struct Player;
+pub enum DoorTransit {
+    Entering { door_x: f32, timer: Timer },
+    Exiting { timer: Timer },
+}
 
-// SUBJECT TO CHANGE
-#[derive(Resource)]
-pub struct PersistentPlayerData {
-    hp: Health,
+#[derive(Component)]
+pub struct Dying {
+    timer: Timer,
 }
 
-#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Reflect)]
+/// Present while the player is ducking with a shrunk collider.
+#[derive(Component)]
+struct Crouching;
+
+struct WallKick {
+    dir: f32,
+    timer: Timer,
+}
+
+#[derive(Component, Default)]
+struct WallMovement {
+    /// The side of the player touching a wall: -1 for left, 1 for right.
+    contact_dir: Option<f32>,
+    is_sliding: bool,
+    /// Pushes the player away from the wall for a moment after a wall jump.
+    kick: Option<WallKick>,
+}
+
+#[derive(Component, Default)]
+pub enum LedgeGrab {
+    #[default]
+    Free,
+    Hanging {
+        /// Where the player stands once climbed up.
+        top: Vec2,
+    },
+    ClimbingUp {
+        from: Vec2,
+        to: Vec2,
+        timer: Timer,
+    },
+    /// Keeps the player from catching the same ledge right after dropping from it.
+    LettingGo {
+        timer: Timer,
+    },
+}
+
+impl LedgeGrab {
+    pub fn is_holding(&self) -> bool {
+        matches!(self, Self::Hanging { .. } | Self::ClimbingUp { .. })
+    }
+}
+
+#[derive(Component)]
+struct DashAbility {
+    cooldown: Timer,
+    /// Only one dash is allowed between leaving the ground and landing again.
+    is_used_in_air: bool,
+}
+
+impl Default for DashAbility {
+    fn default() -> Self {
+        Self {
+            cooldown: Timer::new(Duration::ZERO, TimerMode::Once),
+            is_used_in_air: false,
+        }
+    }
+}
+
+/// Keeps a jump tapped in mid-air alive so that it still fires on landing, even once released.
+/// Cleared as soon as the jump starts, so that a tap still makes a short jump.
+#[derive(Component)]
+struct JumpBuffer(Timer);
+
+impl Default for JumpBuffer {
+    fn default() -> Self {
+        Self(Timer::new(Duration::ZERO, TimerMode::Once))
+    }
+}
+
+#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Reflect, Debug, Serialize, Deserialize)]
 pub enum PlayerAction {
     MoveLeft,
     MoveRight,
     Jump,
     DropDown,
     EnterDoor,
+    Dash,
+    Attack,
+    Throw,
+}
+
+impl PlayerAction {
+    pub const ALL: [Self; 8] = [
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::Jump,
+        Self::DropDown,
+        Self::EnterDoor,
+        Self::Dash,
+        Self::Attack,
+        Self::Throw,
+    ];
 }
 
-#[derive(Hash, Eq, PartialEq, Clone, Copy)]
-enum PlayerAnimation {
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum PlayerAnimation {
     Idling,
     Running,
     Jumping,
     Falling,
+    CrouchIdling,
+    CrouchWalking,
+    WallSliding,
+    WallJumping,
+    Dashing,
+    Attacking,
+    HangingOnLedge,
+    ClimbingLedge,
+    EnteringDoor,
+    ExitingDoor,
+    Dying,
+    LandingStunned,
+    Hurt,
 }
 
 impl AnimationState for PlayerAnimation {
@@ -63,6 +214,20 @@ impl AnimationState for PlayerAnimation {
             PlayerAnimation::Running => AnimationIndices::new(9, 10),
             PlayerAnimation::Jumping => AnimationIndices::new(1, 1),
             PlayerAnimation::Falling => AnimationIndices::new(2, 2),
+            PlayerAnimation::CrouchIdling => AnimationIndices::new(3, 3),
+            // The sprite sheet only has the one ducking pose, so shuffle between it and a step.
+            PlayerAnimation::CrouchWalking => AnimationIndices::new(3, 4),
+            PlayerAnimation::WallSliding => AnimationIndices::new(18, 18),
+            PlayerAnimation::WallJumping => AnimationIndices::new(20, 20),
+            PlayerAnimation::Dashing => AnimationIndices::new(19, 19),
+            PlayerAnimation::Attacking => AnimationIndices::new(14, 14),
+            PlayerAnimation::HangingOnLedge => AnimationIndices::new(5, 5),
+            PlayerAnimation::ClimbingLedge => AnimationIndices::new(5, 6),
+            PlayerAnimation::EnteringDoor => AnimationIndices::new(22, 22),
+            PlayerAnimation::ExitingDoor => AnimationIndices::new(23, 23),
+            PlayerAnimation::Dying => AnimationIndices::new(16, 16),
+            PlayerAnimation::LandingStunned => AnimationIndices::new(21, 21),
+            PlayerAnimation::Hurt => AnimationIndices::new(4, 4),
         }
     }
 
@@ -72,10 +237,32 @@ impl AnimationState for PlayerAnimation {
             PlayerAnimation::Running => AnimationTimer::new(Duration::from_secs_f32(3f32.recip())),
             PlayerAnimation::Jumping => AnimationTimer::zero(),
             PlayerAnimation::Falling => AnimationTimer::zero(),
+            PlayerAnimation::CrouchIdling => AnimationTimer::zero(),
+            PlayerAnimation::CrouchWalking => {
+                AnimationTimer::new(Duration::from_secs_f32(2f32.recip()))
+            }
+            PlayerAnimation::WallSliding => AnimationTimer::zero(),
+            PlayerAnimation::WallJumping => AnimationTimer::zero(),
+            PlayerAnimation::Dashing => AnimationTimer::zero(),
+            PlayerAnimation::Attacking => AnimationTimer::zero(),
+            PlayerAnimation::HangingOnLedge => AnimationTimer::zero(),
+            PlayerAnimation::ClimbingLedge => AnimationTimer::new(LEDGE_CLIMB_DURATION / 2),
+            PlayerAnimation::EnteringDoor => AnimationTimer::zero(),
+            PlayerAnimation::ExitingDoor => AnimationTimer::zero(),
+            PlayerAnimation::Dying => AnimationTimer::zero(),
+            PlayerAnimation::LandingStunned => AnimationTimer::zero(),
+            PlayerAnimation::Hurt => AnimationTimer::zero(),
         }
     }
 }
 
+/// Sent when a player jumps, off the ground or in the air.
+#[derive(Event)]
+pub struct PlayerJumpEvent {
+    pub player: Entity,
+    pub is_air_jump: bool,
+}
+
 #[derive(Event)]
 pub struct PlayerSpawnEvent {
     pub pos: Vec2,
@@ -84,145 +271,689 @@ pub struct PlayerSpawnEvent {
 pub fn on_player_spawn(
     mut player_spawn_evr: EventReader<PlayerSpawnEvent>,
     mut cmds: Commands,
-    player_assets: Res<TextureAtlasOwner<Player>>,
-    persistent_player_data: Option<Res<PersistentPlayerData>>,
+    character_atlases: Res<CharacterAtlases>,
+    selected_character: Res<SelectedCharacter>,
+    selected_modifiers: Res<SelectedModifiers>,
+    player_count: Res<PlayerCount>,
+    run_state: Option<Res<RunState>>,
+    run_seed: Res<RunSeed>,
+    controls: Res<Controls>,
+    gamepads: Res<Gamepads>,
+) {
+    let Some(&PlayerSpawnEvent { pos }) = player_spawn_evr.read().next() else {
+        return;
+    };
+    let run_state = run_state.map_or_else(
+        || {
+            let run_state = RunState::new(
+                run_seed.0,
+                vec![selected_character.0; player_count.0],
+                selected_modifiers.0.clone(),
+            );
+            cmds.insert_resource(run_state.clone());
+            run_state
+        },
+        |run_state| run_state.clone(),
+    );
+
+    for (idx, player_state) in run_state.players.iter().enumerate() {
+        let character = player_state.character;
+        let player_assets = character_atlases.get(character);
+        let devices = InputDevices::for_player(idx, run_state.players.len(), &gamepads);
+        let offset =
+            (idx as f32 - (run_state.players.len() - 1) as f32 / 2.) * PLAYER_SPAWN_SPACING;
+
+        cmds.spawn((
+            (
+                Player(idx),
+                StateScoped(GameState::Playing),
+                AnimationIndices::default(),
+                AnimationTimer::default(),
+                Flippable::default(),
+                KeepOnDeath,
+                if player_state.hp.0 > 0 {
+                    player_state.hp
+                } else {
+                    DOWNED_RESPAWN_HEALTH
+                },
+                player_state.max_health,
+                player_state.inventory.clone(),
+                character,
+                MovementTuning::default(),
+                devices,
+            ),
+            SpriteBundle {
+                sprite: Sprite {
+                    color: PLAYER_TINTS[idx],
+                    ..default()
+                },
+                texture: player_assets.texture(),
+                transform: Transform::from_translation((pos + offset * Vec2::X).extend(PLAYER_Z)),
+                ..default()
+            },
+            TextureAtlas {
+                layout: player_assets.layout(),
+                index: 0,
+            },
+            InputManagerBundle::with_map(controls.input_map(devices)),
+            (
+                RigidBody::Dynamic,
+                LockedAxes::ROTATION_LOCKED,
+                Collider::capsule_y(PLAYER_COLLIDER_HALF_HEIGHT, PLAYER_COLLDIER_RADIUS),
+                Friction::coefficient(0.),
+                CollisionGroups::new(CHARACTER_GROUP, Group::ALL),
+            ),
+            TnuaRapier2dIOBundle::default(),
+            TnuaControllerBundle::default(),
+            TnuaSimpleAirActionsCounter::default(),
+            TnuaSimpleFallThroughPlatformsHelper::default(),
+            TnuaGhostSensor::default(),
+            TnuaRapier2dSensorShape(Collider::cuboid(PLAYER_COLLDIER_RADIUS - 2., 0.)),
+            TnuaAnimatingState::<PlayerAnimation>::default(),
+            (
+                WallMovement::default(),
+                LedgeGrab::default(),
+                DashAbility::default(),
+                JumpBuffer::default(),
+                FallTracker::default(),
+                MeleeAttacker::default(),
+                Thrower::default(),
+                ThrowAim::default(),
+            ),
+            DoorTransit::Exiting {
+                timer: Timer::new(DOOR_TRANSIT_DURATION, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+fn detect_walls(
+    mut player_qry: Query<(&Transform, &mut WallMovement), With<Player>>,
+    tile_qry: Query<(), With<Tile>>,
+    rapier_ctx: Res<RapierContext>,
 ) {
-    cmds.spawn((
+    let is_tile = |id| tile_qry.contains(id);
+    let filter = QueryFilter::new().exclude_sensors().predicate(&is_tile);
+    let probe = Collider::cuboid(1., PLAYER_COLLIDER_HALF_HEIGHT);
+
+    for (player_xform, mut player_wall) in &mut player_qry {
+        player_wall.contact_dir = [-1., 1.].into_iter().find(|&dir| {
+            rapier_ctx
+                .cast_shape(
+                    player_xform.translation.truncate(),
+                    0.,
+                    dir * Vec2::X,
+                    &probe,
+                    ShapeCastOptions::with_max_time_of_impact(
+                        PLAYER_COLLDIER_RADIUS + WALL_CONTACT_DISTANCE,
+                    ),
+                    filter,
+                )
+                .is_some()
+        });
+    }
+}
+
+fn update_ledge_grab(
+    time: Res<Time>,
+    mut player_qry: Query<
         (
-            Player,
-            StateScoped(GameState::Playing),
-            AnimationIndices::default(),
-            AnimationTimer::default(),
-            Flippable::default(),
-            persistent_player_data
-                .map(|data| data.hp)
-                .unwrap_or(PLAYER_MAX_HEALTH),
+            Entity,
+            &ActionState<PlayerAction>,
+            &TnuaController,
+            &mut Transform,
+            &mut Velocity,
+            &mut LedgeGrab,
+            &WallMovement,
+            Option<&DoorTransit>,
+            (Has<Dying>, Has<HitStun>),
         ),
-        SpriteBundle {
-            texture: player_assets.texture(),
-            transform: Transform::from_translation(
-                player_spawn_evr.read().next().unwrap().pos.extend(PLAYER_Z),
-            ),
-            ..default()
-        },
-        TextureAtlas {
-            layout: player_assets.layout(),
-            index: 0,
-        },
-        InputManagerBundle::with_map(InputMap::new([
-            (PlayerAction::MoveLeft, KeyCode::KeyA),
-            (PlayerAction::MoveRight, KeyCode::KeyD),
-            (PlayerAction::Jump, KeyCode::KeyW),
-            (PlayerAction::DropDown, KeyCode::KeyS),
-            (PlayerAction::EnterDoor, KeyCode::Space),
-        ])),
-        RigidBody::Dynamic,
-        LockedAxes::ROTATION_LOCKED,
-        Collider::capsule_y(PLAYER_COLLIDER_HALF_HEIGHT, PLAYER_COLLDIER_RADIUS),
-        Friction::coefficient(0.),
-        TnuaRapier2dIOBundle::default(),
-        TnuaControllerBundle::default(),
-        TnuaSimpleAirActionsCounter::default(),
-        TnuaSimpleFallThroughPlatformsHelper::default(),
-        TnuaGhostSensor::default(),
-        TnuaRapier2dSensorShape(Collider::cuboid(PLAYER_COLLDIER_RADIUS - 2., 0.)),
-        TnuaAnimatingState::<PlayerAnimation>::default(),
-    ));
+        With<Player>,
+    >,
+    tile_qry: Query<&Transform, (With<Tile>, Without<Player>)>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+) {
+    for (
+        player_id,
+        player_in,
+        player_kcc,
+        mut player_xform,
+        mut player_velocity,
+        mut player_ledge,
+        player_wall,
+        player_transit,
+        (player_is_dying, player_is_hit_stunned),
+    ) in &mut player_qry
+    {
+        let player_pos = player_xform.translation.truncate();
+        // Knocked off the ledge, keeping the knockback.
+        if player_is_hit_stunned && player_ledge.is_holding() {
+            cmds.entity(player_id).insert(GravityScale(1.));
+            *player_ledge = LedgeGrab::LettingGo {
+                timer: Timer::new(LEDGE_LET_GO_DURATION, TimerMode::Once),
+            };
+            continue;
+        }
+
+        match &mut *player_ledge {
+            LedgeGrab::Free => {
+                let player_is_airborne = player_kcc
+                    .concrete_basis::<TnuaBuiltinWalk>()
+                    .map_or(true, |(_, basis_state)| {
+                        basis_state.standing_on_entity().is_none()
+                    });
+                let Some(dir) = player_wall.contact_dir else {
+                    continue;
+                };
+                let player_holds_toward_wall = player_in.pressed(if dir < 0. {
+                    &PlayerAction::MoveLeft
+                } else {
+                    &PlayerAction::MoveRight
+                });
+                if player_transit.is_some()
+                    || player_is_dying
+                    || player_is_hit_stunned
+                    || !player_is_airborne
+                    || !player_holds_toward_wall
+                    || player_in.pressed(&PlayerAction::DropDown)
+                    || player_velocity.linvel.y > 0.
+                {
+                    continue;
+                }
+
+                let is_tile = |id| tile_qry.contains(id);
+                let filter = QueryFilter::new().exclude_sensors().predicate(&is_tile);
+                let Some(tile_xform) = rapier_ctx
+                    .cast_ray(
+                        player_pos,
+                        dir * Vec2::X,
+                        PLAYER_COLLDIER_RADIUS + WALL_CONTACT_DISTANCE,
+                        true,
+                        filter,
+                    )
+                    .and_then(|(tile_id, _)| tile_qry.get(tile_id).ok())
+                else {
+                    continue;
+                };
+
+                let ledge =
+                    tile_xform.translation.truncate() + TILE_SIZE / 2. * Vec2::new(-dir, 1.);
+                if !(0. ..=LEDGE_REACH).contains(&(ledge.y - player_pos.y)) {
+                    continue;
+                }
+                // Only ledges with room to stand on can be grabbed.
+                let top = ledge + Vec2::new(dir * TILE_SIZE.x / 4., PLAYER_FLOAT_HEIGHT);
+                if rapier_ctx
+                    .intersection_with_shape(
+                        top,
+                        0.,
+                        &Collider::capsule_y(PLAYER_COLLIDER_HALF_HEIGHT, PLAYER_COLLDIER_RADIUS),
+                        filter,
+                    )
+                    .is_some()
+                {
+                    continue;
+                }
+
+                player_xform.translation.y = ledge.y - LEDGE_HANG_DEPTH;
+                player_velocity.linvel = Vec2::ZERO;
+                cmds.entity(player_id).insert(GravityScale(0.));
+                *player_ledge = LedgeGrab::Hanging { top };
+            }
+            &mut LedgeGrab::Hanging { top } => {
+                player_velocity.linvel = Vec2::ZERO;
+                if player_in.just_pressed(&PlayerAction::Jump) {
+                    *player_ledge = LedgeGrab::ClimbingUp {
+                        from: player_pos,
+                        to: top,
+                        timer: Timer::new(LEDGE_CLIMB_DURATION, TimerMode::Once),
+                    };
+                } else if player_in.just_pressed(&PlayerAction::DropDown) || player_is_dying {
+                    cmds.entity(player_id).insert(GravityScale(1.));
+                    *player_ledge = LedgeGrab::LettingGo {
+                        timer: Timer::new(LEDGE_LET_GO_DURATION, TimerMode::Once),
+                    };
+                }
+            }
+            LedgeGrab::ClimbingUp { from, to, timer } => {
+                player_velocity.linvel = Vec2::ZERO;
+                // Up along the wall first, then over the corner.
+                let t = timer.tick(time.delta()).fraction() * 2.;
+                let pos = if t < 1. {
+                    Vec2::new(from.x, from.y.lerp(to.y, t))
+                } else {
+                    Vec2::new(from.x.lerp(to.x, t - 1.), to.y)
+                };
+                player_xform.translation = pos.extend(player_xform.translation.z);
+
+                if timer.finished() {
+                    cmds.entity(player_id).insert(GravityScale(1.));
+                    *player_ledge = LedgeGrab::Free;
+                }
+            }
+            LedgeGrab::LettingGo { timer } => {
+                if timer.tick(time.delta()).finished() {
+                    *player_ledge = LedgeGrab::Free;
+                }
+            }
+        }
+    }
+}
+
+fn update_crouching(
+    mut player_qry: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &TnuaController,
+            &Transform,
+            &mut Sprite,
+            Has<Crouching>,
+            Option<&DoorTransit>,
+            Has<Dying>,
+        ),
+        With<Player>,
+    >,
+    tile_qry: Query<(), With<Tile>>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+) {
+    for (
+        player_id,
+        player_in,
+        player_kcc,
+        player_xform,
+        mut player_sprite,
+        player_is_crouching,
+        player_transit,
+        player_is_dying,
+    ) in &mut player_qry
+    {
+        // Ghost platforms are left to `DropDown` falling through them.
+        let player_is_on_tile = player_kcc
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .and_then(|(_, basis_state)| basis_state.standing_on_entity())
+            .is_some_and(|ground_id| tile_qry.contains(ground_id));
+        let player_wants_to_crouch = player_transit.is_none()
+            && !player_is_dying
+            && player_is_on_tile
+            && player_in.pressed(&PlayerAction::DropDown);
+        if player_wants_to_crouch == player_is_crouching {
+            continue;
+        }
+
+        let standing_collider =
+            Collider::capsule_y(PLAYER_COLLIDER_HALF_HEIGHT, PLAYER_COLLDIER_RADIUS);
+        if player_wants_to_crouch {
+            cmds.entity(player_id)
+                .insert((Crouching, Collider::capsule_y(0., PLAYER_COLLDIER_RADIUS)));
+        } else {
+            // Stay down while there is no headroom to stand back up.
+            let is_tile = |id| tile_qry.contains(id);
+            if rapier_ctx
+                .intersection_with_shape(
+                    player_xform.translation.truncate()
+                        + (PLAYER_FLOAT_HEIGHT - CROUCH_FLOAT_HEIGHT) * Vec2::Y,
+                    0.,
+                    &standing_collider,
+                    QueryFilter::new().exclude_sensors().predicate(&is_tile),
+                )
+                .is_some()
+            {
+                continue;
+            }
+            cmds.entity(player_id)
+                .remove::<Crouching>()
+                .insert(standing_collider);
+        }
+
+        // Keep the feet on the ground while the body floats lower.
+        player_sprite.anchor = Anchor::Custom(Vec2::new(
+            player_sprite.anchor.as_vec().x,
+            if player_wants_to_crouch {
+                (CROUCH_FLOAT_HEIGHT - PLAYER_FLOAT_HEIGHT) / PLAYER_SPRITE_SIZE.y as f32
+            } else {
+                0.
+            },
+        ));
+    }
 }
 
 fn player_movement(
+    time: Res<Time>,
     mut player_qry: Query<
         (
             Entity,
             &ActionState<PlayerAction>,
             &mut TnuaController,
-            &mut TnuaSimpleAirActionsCounter,
-            &mut TnuaSimpleFallThroughPlatformsHelper,
-            &mut TnuaProximitySensor,
-            &TnuaGhostSensor,
-            &AnimationIndices,
-            &Health,
+            (&mut TnuaSimpleAirActionsCounter, &mut JumpBuffer),
+            (
+                &mut TnuaSimpleFallThroughPlatformsHelper,
+                &mut TnuaProximitySensor,
+                &TnuaGhostSensor,
+            ),
             &mut Flippable,
+            &Transform,
+            &mut Velocity,
+            &mut WallMovement,
+            (&mut DashAbility, Option<&Iframes>),
+            &LedgeGrab,
+            (&MovementTuning, &Inventory),
+            Has<Crouching>,
+            Option<&DoorTransit>,
+            (Has<Dying>, Has<LandingStun>, Has<HitStun>),
         ),
         With<Player>,
     >,
-    door_qry: Query<(&Door, Entity), (With<Collider>, With<Sensor>)>,
-    rapier_ctx: Res<RapierContext>,
-    mut next_state: ResMut<NextState<GameState>>,
+    mut jump_evw: EventWriter<PlayerJumpEvent>,
     mut cmds: Commands,
 ) {
-    let (
+    for (
         player_id,
         player_in,
         mut player_kcc,
-        mut player_air_actions_count,
-        mut player_ghost_platforms_helper,
-        mut player_prox_sensor,
-        player_ghost_sensor,
-        player_animation_idxs,
-        player_hp,
+        (mut player_air_actions_count, mut player_jump_buffer),
+        (mut player_ghost_platforms_helper, mut player_prox_sensor, player_ghost_sensor),
         mut player_flippable,
-    ) = player_qry.single_mut();
-
-    player_kcc.basis(TnuaBuiltinWalk {
-        max_slope: FRAC_PI_4,
-        spring_dampening: 0.5,
-        float_height: PLAYER_COLLIDER_HALF_HEIGHT + PLAYER_COLLDIER_RADIUS + 14.,
-        air_acceleration: 5. * TILE_SIZE.x,
-        acceleration: 5. * TILE_SIZE.x,
-        desired_velocity: 4.
-            * TILE_SIZE.x
-            * if player_in.pressed(&PlayerAction::MoveLeft)
-                && player_in.released(&PlayerAction::MoveRight)
-            {
-                player_flippable.flip_x = true;
-                -Vec3::X
-            } else if player_in.pressed(&PlayerAction::MoveRight)
-                && player_in.released(&PlayerAction::MoveLeft)
-            {
-                player_flippable.flip_x = false;
-                Vec3::X
-            } else {
-                Vec3::ZERO
-            },
-        ..default()
-    });
+        player_xform,
+        mut player_velocity,
+        mut player_wall,
+        (mut player_dash, player_iframes),
+        player_ledge,
+        (player_tuning, player_inventory),
+        player_is_crouching,
+        player_transit,
+        (player_is_dying, player_is_stunned, player_is_hit_stunned),
+    ) in &mut player_qry
+    {
+        let player_has_control = player_transit.is_none()
+            && !player_is_dying
+            && !player_is_stunned
+            && !player_is_hit_stunned
+            && !player_ledge.is_holding();
+
+        if let Some(kick) = &mut player_wall.kick {
+            if kick.timer.tick(time.delta()).finished() {
+                player_wall.kick = None;
+            }
+        }
 
-    player_air_actions_count.update(&player_kcc);
+        let player_run_speed =
+            player_tuning.run_speed * TILE_SIZE.x * player_inventory.run_speed_factor();
+        let player_standing_float_height = player_tuning.float_height * TILE_SIZE.y;
+        let (player_float_height, player_speed) = if player_is_crouching {
+            (
+                player_standing_float_height - (PLAYER_FLOAT_HEIGHT - CROUCH_FLOAT_HEIGHT),
+                player_run_speed * CROUCH_SPEED_FACTOR,
+            )
+        } else {
+            (player_standing_float_height, player_run_speed)
+        };
 
-    if player_in.pressed(&PlayerAction::Jump) {
-        player_kcc.action(TnuaBuiltinJump {
-            height: TILE_SIZE.y * 1.5,
-            allow_in_air: player_air_actions_count.air_count_for(TnuaBuiltinJump::NAME) < 2,
+        player_kcc.basis(TnuaBuiltinWalk {
+            max_slope: player_tuning.max_slope.to_radians(),
+            spring_dampening: player_tuning.spring_dampening,
+            float_height: player_float_height,
+            air_acceleration: player_tuning.air_acceleration * TILE_SIZE.x,
+            acceleration: player_tuning.acceleration * TILE_SIZE.x,
+            coyote_time: player_tuning.coyote_time,
+            desired_velocity: player_speed
+                * if let Some(&DoorTransit::Entering { door_x, .. }) = player_transit {
+                    ((door_x - player_xform.translation.x) / TILE_SIZE.x).clamp(-1., 1.) * Vec3::X
+                } else if player_is_hit_stunned {
+                    // Let the knockback carry the player instead of braking against it.
+                    if player_speed > 0. {
+                        player_velocity.linvel.x / player_speed * Vec3::X
+                    } else {
+                        Vec3::ZERO
+                    }
+                } else if !player_has_control {
+                    Vec3::ZERO
+                } else if let Some(kick) = &player_wall.kick {
+                    player_flippable.flip_x = kick.dir < 0.;
+                    kick.dir * Vec3::X
+                } else if player_in.pressed(&PlayerAction::MoveLeft)
+                    && player_in.released(&PlayerAction::MoveRight)
+                {
+                    player_flippable.flip_x = true;
+                    -Vec3::X
+                } else if player_in.pressed(&PlayerAction::MoveRight)
+                    && player_in.released(&PlayerAction::MoveLeft)
+                {
+                    player_flippable.flip_x = false;
+                    Vec3::X
+                } else {
+                    Vec3::ZERO
+                },
             ..default()
         });
+
+        player_air_actions_count.update(&player_kcc);
+        if player_kcc.action_flow_status().just_starting() == Some(TnuaBuiltinJump::NAME) {
+            *player_jump_buffer = JumpBuffer::default();
+            jump_evw.send(PlayerJumpEvent {
+                player: player_id,
+                is_air_jump: player_air_actions_count.air_count_for(TnuaBuiltinJump::NAME) > 1,
+            });
+        }
+
+        let player_is_airborne = player_kcc
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .map_or(true, |(_, basis_state)| {
+                basis_state.standing_on_entity().is_none()
+            });
+        let player_holds_toward_wall = player_wall.contact_dir.is_some_and(|dir| {
+            player_in.pressed(if dir < 0. {
+                &PlayerAction::MoveLeft
+            } else {
+                &PlayerAction::MoveRight
+            })
+        });
+
+        player_wall.is_sliding = player_has_control
+            && player_is_airborne
+            && player_holds_toward_wall
+            && player_wall.kick.is_none()
+            && player_velocity.linvel.y <= 0.;
+        if player_wall.is_sliding {
+            player_velocity.linvel.y = player_velocity.linvel.y.max(-WALL_SLIDE_SPEED);
+        }
+
+        player_jump_buffer.0.tick(time.delta());
+        if player_is_airborne && player_in.just_pressed(&PlayerAction::Jump) {
+            player_jump_buffer.0 =
+                Timer::from_seconds(player_tuning.jump_buffer_time, TimerMode::Once);
+        }
+        let player_wants_to_jump =
+            player_in.pressed(&PlayerAction::Jump) || !player_jump_buffer.0.finished();
+
+        if player_has_control && player_wants_to_jump {
+            if player_wall.is_sliding && player_in.just_pressed(&PlayerAction::Jump) {
+                let kick_dir = -player_wall.contact_dir.unwrap();
+                player_velocity.linvel.x = kick_dir * player_speed;
+                player_wall.is_sliding = false;
+                player_wall.kick = Some(WallKick {
+                    dir: kick_dir,
+                    timer: Timer::new(WALL_KICK_DURATION, TimerMode::Once),
+                });
+            }
+            player_kcc.action(TnuaBuiltinJump {
+                height: player_tuning.jump_height
+                    * TILE_SIZE.y
+                    * player_inventory.jump_height_factor(),
+                // Starts with the same press as `JumpBuffer`, so the two windows line up.
+                input_buffer_time: player_tuning.jump_buffer_time,
+                // The jump off the ground counts as the first air action.
+                allow_in_air: player_wall.kick.is_some()
+                    || player_air_actions_count.air_count_for(TnuaBuiltinJump::NAME)
+                        < 1 + player_tuning.air_jumps + player_inventory.extra_air_jumps(),
+                ..default()
+            });
+        }
+
+        player_dash.cooldown.tick(time.delta());
+        if !player_is_airborne {
+            player_dash.is_used_in_air = false;
+        }
+        let player_is_dashing = player_kcc.action_name() == Some(TnuaBuiltinDash::NAME);
+        let player_can_dash = player_dash.cooldown.finished()
+            && !(player_is_airborne && player_dash.is_used_in_air)
+            && player_in.just_pressed(&PlayerAction::Dash);
+
+        if player_has_control && player_in.pressed(&PlayerAction::Dash) {
+            if !player_is_dashing && player_can_dash {
+                player_dash.cooldown = Timer::new(DASH_COOLDOWN, TimerMode::Once);
+                player_dash.is_used_in_air = player_is_airborne;
+                // Damage iframes that outlast the dash are left alone.
+                if player_iframes
+                    .map_or(true, |iframes| iframes.remaining() < DASH_IFRAMES_DURATION)
+                {
+                    cmds.entity(player_id)
+                        .insert(Iframes::new(DASH_IFRAMES_DURATION));
+                }
+            }
+            if player_is_dashing || player_can_dash {
+                player_kcc.action(TnuaBuiltinDash {
+                    displacement: DASH_DISTANCE
+                        * if player_flippable.flip_x {
+                            -Vec3::X
+                        } else {
+                            Vec3::X
+                        },
+                    allow_in_air: true,
+                    speed: DASH_SPEED,
+                    ..default()
+                });
+            }
+        }
+
+        // Decided from the controller rather than the animation so that replays stay deterministic.
+        let player_is_rising = matches!(
+            player_kcc.concrete_action::<TnuaBuiltinJump>(),
+            Some((_, jump_state)) if !matches!(
+                jump_state,
+                TnuaBuiltinJumpState::NoJump | TnuaBuiltinJumpState::FallSection
+            )
+        );
+
+        let mut ghost_platforms_handle = player_ghost_platforms_helper.with(
+            &mut player_prox_sensor,
+            player_ghost_sensor,
+            PLAYER_COLLIDER_HALF_HEIGHT + PLAYER_COLLDIER_RADIUS,
+        );
+
+        if player_has_control && player_in.pressed(&PlayerAction::DropDown) {
+            ghost_platforms_handle.try_falling(true);
+        } else if !player_is_rising {
+            ghost_platforms_handle.dont_fall();
+        }
     }
+}
 
-    let mut ghost_platforms_handle = player_ghost_platforms_helper.with(
-        &mut player_prox_sensor,
-        player_ghost_sensor,
-        PLAYER_COLLIDER_HALF_HEIGHT + PLAYER_COLLDIER_RADIUS,
-    );
+fn update_door_transit(
+    time: Res<Time>,
+    mut player_qry: Query<(
+        Entity,
+        &Player,
+        Option<&mut DoorTransit>,
+        &mut Sprite,
+        &Health,
+        &MaxHealth,
+        &Inventory,
+        Has<Dying>,
+    )>,
+    level_info: Res<LevelInfo>,
+    mut run_state: ResMut<RunState>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut cmds: Commands,
+) {
+    let mut has_anyone_just_entered = false;
+    let mut have_all_entered = true;
 
-    if player_in.pressed(&PlayerAction::DropDown) {
-        ghost_platforms_handle.try_falling(true);
-    } else if *player_animation_idxs != PlayerAnimation::Jumping.indices() {
-        ghost_platforms_handle.dont_fall();
-    }
-
-    if player_in.pressed(&PlayerAction::EnterDoor)
-        && rapier_ctx.intersection_pair(
-            player_id,
-            door_qry
-                .iter()
-                .filter(|(&door, _)| door == Door::Exit)
-                .map(|(_, door_id)| door_id)
-                .next()
-                .unwrap(),
-        ) == Some(true)
+    for (player_id, _, player_transit, mut player_sprite, _, _, _, player_is_dying) in
+        &mut player_qry
     {
-        cmds.insert_resource(PersistentPlayerData { hp: *player_hp });
-        next_state.set(GameState::Transition);
+        match player_transit.map(Mut::into_inner) {
+            Some(DoorTransit::Entering { timer, .. }) => {
+                timer.tick(time.delta());
+                player_sprite.color.set_alpha(timer.fraction_remaining());
+                has_anyone_just_entered |= timer.just_finished();
+                have_all_entered &= timer.finished();
+            }
+            Some(DoorTransit::Exiting { timer }) => {
+                timer.tick(time.delta());
+                player_sprite.color.set_alpha(timer.fraction());
+                if timer.just_finished() {
+                    cmds.entity(player_id).remove::<DoorTransit>();
+                }
+                have_all_entered = false;
+            }
+            None => have_all_entered &= player_is_dying,
+        }
+    }
+    if !has_anyone_just_entered || !have_all_entered {
+        return;
+    }
+
+    run_state.award_level_gold(&level_info);
+    run_state.stats.levels_cleared += 1;
+    // Whoever is missing was downed.
+    for player_state in &mut run_state.players {
+        player_state.hp = Health(0);
+    }
+    for (_, &Player(idx), _, _, &player_hp, &player_max_hp, player_inventory, _) in &player_qry {
+        let player_state = &mut run_state.players[idx];
+        player_state.max_health = player_max_hp;
+        player_state.hp = player_hp;
+        player_state.inventory = player_inventory.clone();
+    }
+    next_state.set(GameState::Transition);
+}
+
+fn start_dying(
+    mut death_evr: EventReader<DeathEvent>,
+    player_qry: Query<(), (With<Player>, Without<Dying>)>,
+    mut cmds: Commands,
+) {
+    for death in death_evr.read() {
+        if player_qry.contains(death.entity) {
+            cmds.entity(death.entity)
+                .remove::<DoorTransit>()
+                .insert(Dying {
+                    timer: Timer::new(PLAYER_DEATH_DURATION, TimerMode::Once),
+                });
+        }
+    }
+}
+
+fn update_dying(
+    time: Res<Time>,
+    mut player_qry: Query<(Entity, &mut Dying, &mut Sprite, Option<&LastHitBy>), With<Player>>,
+    living_player_qry: Query<(), (With<Player>, Without<Dying>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut cmds: Commands,
+) {
+    for (player_id, mut player_dying, mut player_sprite, player_last_hit) in &mut player_qry {
+        player_dying.timer.tick(time.delta());
+        player_sprite
+            .color
+            .set_alpha(player_dying.timer.fraction_remaining());
+
+        if !player_dying.timer.just_finished() {
+            continue;
+        }
+        // Downed players sit out the rest of the level while anyone else is still standing.
+        if !living_player_qry.is_empty() {
+            cmds.entity(player_id).despawn_recursive();
+            continue;
+        }
+        cmds.insert_resource(GameOverInfo {
+            cause: player_last_hit
+                .map(|last_hit| last_hit.0.clone())
+                .unwrap_or_else(|| String::from("Unknown")),
+        });
+        next_state.set(GameState::GameOver);
     }
 }
 
@@ -233,68 +964,95 @@ fn player_animation(
             &TnuaController,
             &mut AnimationIndices,
             &mut AnimationTimer,
+            &Character,
+            &WallMovement,
+            &LedgeGrab,
+            Has<Crouching>,
+            Has<Attacking>,
+            Option<&DoorTransit>,
+            Has<Dying>,
+            Has<LandingStun>,
+            Has<HitStun>,
         ),
         With<Player>,
     >,
 ) {
-    let (
+    for (
         mut player_animating_state,
         player_kcc,
         mut player_animation_idxs,
         mut player_animation_timer,
-    ) = player_qry.single_mut();
-    match player_animating_state.update_by_discriminant({
-        match player_kcc.action_name() {
-            Some(TnuaBuiltinJump::NAME) => {
-                match player_kcc.concrete_action::<TnuaBuiltinJump>().unwrap().1 {
-                    TnuaBuiltinJumpState::NoJump => return,
-                    TnuaBuiltinJumpState::StartingJump { .. }
-                    | TnuaBuiltinJumpState::SlowDownTooFastSlopeJump { .. }
-                    | TnuaBuiltinJumpState::MaintainingJump
-                    | TnuaBuiltinJumpState::StoppedMaintainingJump => PlayerAnimation::Jumping,
-                    TnuaBuiltinJumpState::FallSection => PlayerAnimation::Falling,
+        player_character,
+        player_wall,
+        player_ledge,
+        player_is_crouching,
+        player_is_attacking,
+        player_transit,
+        player_is_dying,
+        player_is_stunned,
+        player_is_hit_stunned,
+    ) in &mut player_qry
+    {
+        match player_animating_state.update_by_discriminant({
+            match (player_transit, player_kcc.action_name()) {
+                _ if player_is_dying => PlayerAnimation::Dying,
+                (Some(DoorTransit::Entering { .. }), _) => PlayerAnimation::EnteringDoor,
+                (Some(DoorTransit::Exiting { .. }), _) => PlayerAnimation::ExitingDoor,
+                (None, _) if player_is_hit_stunned => PlayerAnimation::Hurt,
+                (None, _) if player_is_stunned => PlayerAnimation::LandingStunned,
+                (None, _) if player_is_attacking => PlayerAnimation::Attacking,
+                (None, _) if matches!(player_ledge, LedgeGrab::Hanging { .. }) => {
+                    PlayerAnimation::HangingOnLedge
                 }
-            }
-            _ => {
-                let Some((_, basis_state)) = player_kcc.concrete_basis::<TnuaBuiltinWalk>() else {
-                    return;
-                };
-                if basis_state.standing_on_entity().is_none() {
-                    PlayerAnimation::Falling
-                } else if basis_state.running_velocity.x.abs() > 0. {
-                    PlayerAnimation::Running
-                } else {
-                    PlayerAnimation::Idling
+                (None, _) if matches!(player_ledge, LedgeGrab::ClimbingUp { .. }) => {
+                    PlayerAnimation::ClimbingLedge
+                }
+                (None, Some(TnuaBuiltinDash::NAME)) => PlayerAnimation::Dashing,
+                (None, _) if player_wall.kick.is_some() => PlayerAnimation::WallJumping,
+                (None, _) if player_wall.is_sliding => PlayerAnimation::WallSliding,
+                (None, Some(TnuaBuiltinJump::NAME)) => {
+                    match player_kcc.concrete_action::<TnuaBuiltinJump>().unwrap().1 {
+                        TnuaBuiltinJumpState::NoJump => continue,
+                        TnuaBuiltinJumpState::StartingJump { .. }
+                        | TnuaBuiltinJumpState::SlowDownTooFastSlopeJump { .. }
+                        | TnuaBuiltinJumpState::MaintainingJump
+                        | TnuaBuiltinJumpState::StoppedMaintainingJump => PlayerAnimation::Jumping,
+                        TnuaBuiltinJumpState::FallSection => PlayerAnimation::Falling,
+                    }
+                }
+                (None, _) => {
+                    let Some((_, basis_state)) = player_kcc.concrete_basis::<TnuaBuiltinWalk>()
+                    else {
+                        continue;
+                    };
+                    let player_is_running = basis_state.running_velocity.x.abs() > 0.;
+                    if basis_state.standing_on_entity().is_none() {
+                        PlayerAnimation::Falling
+                    } else if player_is_crouching && player_is_running {
+                        PlayerAnimation::CrouchWalking
+                    } else if player_is_crouching {
+                        PlayerAnimation::CrouchIdling
+                    } else if player_is_running {
+                        PlayerAnimation::Running
+                    } else {
+                        PlayerAnimation::Idling
+                    }
                 }
             }
-        }
-    }) {
-        TnuaAnimatingStateDirective::Maintain { .. } => (),
-        TnuaAnimatingStateDirective::Alter { state, .. } => {
-            (*player_animation_idxs, *player_animation_timer) = (state.indices(), state.timer());
+        }) {
+            TnuaAnimatingStateDirective::Maintain { .. } => (),
+            TnuaAnimatingStateDirective::Alter { state, .. } => {
+                (*player_animation_idxs, *player_animation_timer) =
+                    player_character.animation(*state);
+            }
         }
     }
 }
 
 pub fn player_plugin(app: &mut App) {
     app.add_event::<PlayerSpawnEvent>()
-        .add_systems(
-            OnEnter(GameState::Setup),
-            |mut cmds: Commands,
-             asset_server: Res<AssetServer>,
-             mut tex_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>| {
-                cmds.insert_resource(TextureAtlasOwner::<Player>::new(
-                    asset_server.load("player.png"),
-                    tex_atlas_layouts.add(TextureAtlasLayout::from_grid(
-                        UVec2::new(80, 110),
-                        9,
-                        3,
-                        None,
-                        None,
-                    )),
-                ));
-            },
-        )
+        .add_event::<PlayerJumpEvent>()
+        .init_resource::<PlayerCount>()
         .add_systems(
             OnEnter(GameState::Playing),
             on_player_spawn.after(level::signal_level_object_spawns),
@@ -307,8 +1065,243 @@ pub fn player_plugin(app: &mut App) {
         )
         .add_systems(
             FixedUpdate,
-            player_movement
-                .in_set(TnuaUserControlsSystemSet)
+            (
+                (
+                    detect_walls,
+                    update_ledge_grab,
+                    update_crouching,
+                    player_movement,
+                )
+                    .chain()
+                    .in_set(TnuaUserControlsSystemSet),
+                (update_door_transit, start_dying, update_dying).after(TnuaUserControlsSystemSet),
+            )
                 .run_if(in_state(GameState::Playing)),
         );
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::testing::{self, set_action},
+        bevy::time::TimeUpdateStrategy,
+        bevy_tnua_rapier2d::TnuaRapier2dPlugin,
+    };
+
+    const SETTLE_TICKS: usize = 200;
+    /// How long after a jump press the jump is given to start.
+    const JUMP_START_TICKS: u32 = 4;
+    const GROUND_HALF_WIDTH: f32 = 5. * TILE_SIZE.x;
+
+    #[derive(Resource, Default)]
+    struct JumpCount(usize);
+
+    fn count_jumps(player_qry: Query<&TnuaController>, mut jump_count: ResMut<JumpCount>) {
+        jump_count.0 += player_qry
+            .iter()
+            .filter(|player_kcc| {
+                player_kcc.action_flow_status().just_starting() == Some(TnuaBuiltinJump::NAME)
+            })
+            .count();
+    }
+
+    fn timestep() -> Duration {
+        Time::<Fixed>::default().timestep()
+    }
+
+    fn ticks(secs: f32) -> u32 {
+        (secs / timestep().as_secs_f32()).round() as u32
+    }
+
+    /// A physics world with a floor whose right edge is at x = 0, and nothing past it.
+    fn test_app() -> App {
+        let mut app = testing::input_app();
+        app.insert_resource({
+            let mut rapier_cfg = RapierConfiguration::new(TILE_SIZE.x);
+            rapier_cfg.timestep_mode = TimestepMode::Fixed {
+                dt: timestep().as_secs_f32(),
+                substeps: 1,
+            };
+            rapier_cfg
+        })
+        .add_plugins((
+            TransformPlugin,
+            HierarchyPlugin,
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(TILE_SIZE.x).in_fixed_schedule(),
+            TnuaRapier2dPlugin::new(FixedUpdate),
+            TnuaControllerPlugin::new(FixedUpdate),
+        ))
+        // One fixed tick per update.
+        .insert_resource(TimeUpdateStrategy::ManualDuration(timestep()))
+        .add_event::<PlayerJumpEvent>()
+        .init_resource::<JumpCount>()
+        .add_systems(
+            FixedUpdate,
+            (player_movement, count_jumps)
+                .chain()
+                .in_set(TnuaUserControlsSystemSet),
+        );
+
+        app.world_mut().spawn((
+            TransformBundle::from_transform(Transform::from_xyz(
+                -GROUND_HALF_WIDTH,
+                -TILE_SIZE.y / 2.,
+                0.,
+            )),
+            Collider::cuboid(GROUND_HALF_WIDTH, TILE_SIZE.y / 2.),
+        ));
+        app
+    }
+
+    /// Spawns a player without air jumps, so that only ground jumps can succeed.
+    fn spawn_player(app: &mut App, pos: Vec2) -> Entity {
+        app.world_mut()
+            .spawn((
+                (
+                    Player(0),
+                    Flippable::default(),
+                    Inventory::default(),
+                    MovementTuning {
+                        air_jumps: 0,
+                        ..default()
+                    },
+                ),
+                TransformBundle::from_transform(Transform::from_translation(pos.extend(0.))),
+                testing::player_input(InputDevices::KeyboardAndMouse),
+                (
+                    RigidBody::Dynamic,
+                    LockedAxes::ROTATION_LOCKED,
+                    Collider::capsule_y(PLAYER_COLLIDER_HALF_HEIGHT, PLAYER_COLLDIER_RADIUS),
+                    Friction::coefficient(0.),
+                ),
+                TnuaRapier2dIOBundle::default(),
+                TnuaControllerBundle::default(),
+                TnuaSimpleAirActionsCounter::default(),
+                TnuaSimpleFallThroughPlatformsHelper::default(),
+                TnuaGhostSensor::default(),
+                TnuaRapier2dSensorShape(Collider::cuboid(PLAYER_COLLDIER_RADIUS - 2., 0.)),
+                (
+                    WallMovement::default(),
+                    LedgeGrab::default(),
+                    DashAbility::default(),
+                    JumpBuffer::default(),
+                ),
+            ))
+            .id()
+    }
+
+    fn is_airborne(app: &App, player_id: Entity) -> bool {
+        app.world()
+            .get::<TnuaController>(player_id)
+            .unwrap()
+            .concrete_basis::<TnuaBuiltinWalk>()
+            .map_or(true, |(_, basis_state)| {
+                basis_state.standing_on_entity().is_none()
+            })
+    }
+
+    /// Returns how many ticks it took.
+    fn run_until(app: &mut App, condition: impl Fn(&App) -> bool) -> u32 {
+        for tick in 0..SETTLE_TICKS {
+            if condition(app) {
+                return tick as u32;
+            }
+            app.update();
+        }
+        panic!("condition not met within {SETTLE_TICKS} ticks");
+    }
+
+    fn run_ticks(app: &mut App, ticks: u32) {
+        for _ in 0..ticks {
+            app.update();
+        }
+    }
+
+    fn jump_count(app: &App) -> usize {
+        app.world().resource::<JumpCount>().0
+    }
+
+    /// Walks the player off the ledge and presses jump `delay` ticks after their feet leave it.
+    fn jumps_after_walking_off_ledge(delay: u32) -> bool {
+        let mut app = test_app();
+        let player_id = spawn_player(&mut app, Vec2::new(-TILE_SIZE.x, PLAYER_FLOAT_HEIGHT));
+        run_until(&mut app, |app| !is_airborne(app, player_id));
+
+        set_action(&mut app, PlayerAction::MoveRight, true);
+        run_until(&mut app, |app| is_airborne(app, player_id));
+        run_ticks(&mut app, delay);
+        set_action(&mut app, PlayerAction::Jump, true);
+        run_ticks(&mut app, JUMP_START_TICKS);
+        jump_count(&app) > 0
+    }
+
+    /// Drops the player onto the floor with jump pressed `lead` ticks before landing, either
+    /// tapped for a single tick or held, and returns the apex after landing if they jumped.
+    fn jump_apex_after_landing(lead: u32, is_held: bool) -> Option<f32> {
+        let spawn_pos = Vec2::new(-GROUND_HALF_WIDTH, 3. * TILE_SIZE.y);
+        let fall_ticks = {
+            let mut app = test_app();
+            let player_id = spawn_player(&mut app, spawn_pos);
+            run_until(&mut app, |app| !is_airborne(app, player_id))
+        };
+
+        let mut app = test_app();
+        let player_id = spawn_player(&mut app, spawn_pos);
+        run_ticks(&mut app, fall_ticks - lead);
+        set_action(&mut app, PlayerAction::Jump, true);
+        app.update();
+        if !is_held {
+            set_action(&mut app, PlayerAction::Jump, false);
+        }
+        run_ticks(&mut app, lead + JUMP_START_TICKS);
+        if jump_count(&app) == 0 {
+            return None;
+        }
+
+        let mut apex = f32::MIN;
+        while app.world().get::<Velocity>(player_id).unwrap().linvel.y > 0. {
+            apex = apex.max(
+                app.world()
+                    .get::<Transform>(player_id)
+                    .unwrap()
+                    .translation
+                    .y,
+            );
+            app.update();
+        }
+        Some(apex)
+    }
+
+    #[test]
+    fn late_jump_inside_coyote_time_succeeds() {
+        let coyote_ticks = ticks(MovementTuning::default().coyote_time);
+        assert!(jumps_after_walking_off_ledge(coyote_ticks / 3));
+    }
+
+    #[test]
+    fn late_jump_outside_coyote_time_fails() {
+        let coyote_ticks = ticks(MovementTuning::default().coyote_time);
+        assert!(!jumps_after_walking_off_ledge(coyote_ticks * 2));
+    }
+
+    #[test]
+    fn tap_inside_jump_buffer_jumps_on_landing() {
+        let buffer_ticks = ticks(MovementTuning::default().jump_buffer_time);
+        assert!(jump_apex_after_landing(buffer_ticks / 2, false).is_some());
+    }
+
+    #[test]
+    fn tap_outside_jump_buffer_is_dropped() {
+        let buffer_ticks = ticks(MovementTuning::default().jump_buffer_time);
+        assert!(jump_apex_after_landing(buffer_ticks * 2, false).is_none());
+    }
+
+    #[test]
+    fn buffered_tap_jumps_lower_than_a_held_jump() {
+        let buffer_ticks = ticks(MovementTuning::default().jump_buffer_time);
+        let tap_apex = jump_apex_after_landing(buffer_ticks / 2, false).unwrap();
+        let hold_apex = jump_apex_after_landing(buffer_ticks / 2, true).unwrap();
+        assert!(tap_apex < hold_apex);
+    }
+}
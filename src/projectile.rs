@@ -0,0 +1,377 @@
+use {
+    super::{
+        asset_owner::TextureAtlasOwner,
+        combat::{self, Damage, DamageOwner, Health},
+        controls::InputDevices,
+        level,
+        mouse_position::MousePosition,
+        player::{DoorTransit, Dying, Player, PlayerAction},
+        sprite_flip::Flippable,
+        tile::{Tile, TILE_SIZE, TILE_Z},
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    leafwing_input_manager::prelude::*,
+    std::time::Duration,
+};
+
+const PROJECTILE_Z: f32 = TILE_Z + 3.;
+const PROJECTILE_SCALE: f32 = 0.3;
+const PROJECTILE_LIFETIME: Duration = Duration::from_secs(3);
+const EXPLOSION_RADIUS: f32 = TILE_SIZE.x;
+const EXPLOSION_DURATION: Duration = Duration::from_millis(150);
+const THROW_SPEED: f32 = 8. * TILE_SIZE.x;
+const THROW_COOLDOWN: Duration = Duration::from_millis(500);
+/// Players without a mouse throw forward and up, mirrored when facing left.
+const FACING_THROW_AIM: Vec2 = Vec2::new(1., 1.);
+
+/// Characters never block projectiles; projectiles hit them through their damage sensor instead.
+pub const CHARACTER_GROUP: Group = Group::GROUP_2;
+const PROJECTILE_GROUP: Group = Group::GROUP_3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProjectileKind {
+    Rock,
+    Saw,
+    Bomb,
+}
+
+impl ProjectileKind {
+    /// What can be found lying around levels. Rocks are always at hand.
+    pub const PICKUPS: [Self; 2] = [Self::Saw, Self::Bomb];
+
+    fn tex_idx(self) -> usize {
+        match self {
+            ProjectileKind::Rock => 58,
+            ProjectileKind::Saw => 71,
+            ProjectileKind::Bomb => 38,
+        }
+    }
+
+    /// Damage dealt on contact. Bombs only hurt through their explosion.
+    fn damage(self) -> Option<Damage> {
+        match self {
+            ProjectileKind::Rock => Some(Damage::fixed(1, 5.)),
+            ProjectileKind::Saw => Some(Damage::fixed(2, 8.)),
+            ProjectileKind::Bomb => None,
+        }
+    }
+
+    fn restitution(self) -> f32 {
+        match self {
+            ProjectileKind::Rock => 0.6,
+            ProjectileKind::Saw | ProjectileKind::Bomb => 0.,
+        }
+    }
+
+    /// Whether the projectile is destroyed by the first thing it touches rather than bouncing off.
+    fn breaks_on_impact(self) -> bool {
+        match self {
+            ProjectileKind::Rock => false,
+            ProjectileKind::Saw | ProjectileKind::Bomb => true,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    kind: ProjectileKind,
+    owner: Option<Entity>,
+    hitbox: Entity,
+    lifetime: Timer,
+}
+
+#[derive(Component)]
+struct Explosion {
+    timer: Timer,
+}
+
+#[derive(Event)]
+pub struct ProjectileSpawnEvent {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub kind: ProjectileKind,
+    /// Whoever threw or fired the projectile, which it will not hurt.
+    pub owner: Option<Entity>,
+}
+
+/// Lets a character throw the item it is holding, or a rock when empty-handed.
+#[derive(Component)]
+pub struct Thrower {
+    held: Option<ProjectileKind>,
+    cooldown: Timer,
+}
+
+impl Default for Thrower {
+    fn default() -> Self {
+        Self {
+            held: None,
+            cooldown: Timer::new(Duration::ZERO, TimerMode::Once),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ThrowablePickup(ProjectileKind);
+
+#[derive(Event)]
+pub struct ThrowableSpawnEvent {
+    pub pos: Vec2,
+    pub kind: ProjectileKind,
+}
+
+/// Where a thrower is aiming, relative to itself.
+#[derive(Component, Default, Clone, Copy)]
+pub struct ThrowAim(pub Vec2);
+
+fn on_projectile_spawn(
+    mut projectile_spawn_evr: EventReader<ProjectileSpawnEvent>,
+    mut cmds: Commands,
+    tile_assets: Res<TextureAtlasOwner<Tile>>,
+) {
+    for &ProjectileSpawnEvent {
+        pos,
+        vel,
+        kind,
+        owner,
+    } in projectile_spawn_evr.read()
+    {
+        let projectile_id = cmds
+            .spawn((
+                StateScoped(GameState::Playing),
+                SpriteBundle {
+                    transform: Transform::from_translation(pos.extend(PROJECTILE_Z))
+                        .with_scale(Vec3::splat(PROJECTILE_SCALE)),
+                    texture: tile_assets.texture(),
+                    ..default()
+                },
+                TextureAtlas {
+                    layout: tile_assets.layout(),
+                    index: kind.tex_idx(),
+                },
+                RigidBody::Dynamic,
+                Collider::ball(TILE_SIZE.x * 0.4),
+                CollisionGroups::new(PROJECTILE_GROUP, !CHARACTER_GROUP),
+                Restitution::coefficient(kind.restitution()),
+                Velocity::linear(vel),
+                Ccd::enabled(),
+            ))
+            .id();
+
+        let mut hitbox_cmds = cmds.spawn((
+            Collider::ball(TILE_SIZE.x / 2.),
+            ColliderMassProperties::Density(0.),
+            Sensor,
+            Name::new(format!("{kind:?}")),
+            SpatialBundle::default(),
+        ));
+        if let Some(damage) = kind.damage() {
+            hitbox_cmds.insert(damage);
+        }
+        if let Some(owner) = owner {
+            hitbox_cmds.insert(DamageOwner(owner));
+        }
+        let hitbox_id = hitbox_cmds.set_parent(projectile_id).id();
+
+        cmds.entity(projectile_id).insert(Projectile {
+            kind,
+            owner,
+            hitbox: hitbox_id,
+            lifetime: Timer::new(PROJECTILE_LIFETIME, TimerMode::Once),
+        });
+    }
+}
+
+fn on_throwable_spawn(
+    mut throwable_spawn_evr: EventReader<ThrowableSpawnEvent>,
+    mut cmds: Commands,
+    tile_assets: Res<TextureAtlasOwner<Tile>>,
+) {
+    for &ThrowableSpawnEvent { pos, kind } in throwable_spawn_evr.read() {
+        // Resting on the floor of its tile.
+        let pos = pos - Vec2::Y * TILE_SIZE.y * (1. - PROJECTILE_SCALE) / 2.;
+        cmds.spawn((
+            ThrowablePickup(kind),
+            StateScoped(GameState::Playing),
+            SpriteBundle {
+                transform: Transform::from_translation(pos.extend(PROJECTILE_Z))
+                    .with_scale(Vec3::splat(PROJECTILE_SCALE)),
+                texture: tile_assets.texture(),
+                ..default()
+            },
+            TextureAtlas {
+                layout: tile_assets.layout(),
+                index: kind.tex_idx(),
+            },
+            Collider::ball(TILE_SIZE.x / 2.),
+            Sensor,
+        ));
+    }
+}
+
+fn pick_up_throwables(
+    pickup_qry: Query<(Entity, &ThrowablePickup)>,
+    mut player_qry: Query<(Entity, &mut Thrower), (With<Player>, Without<Dying>)>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+) {
+    for (player_id, mut player_thrower) in &mut player_qry {
+        if player_thrower.held.is_some() {
+            continue;
+        }
+        if let Some((pickup_id, &ThrowablePickup(kind))) =
+            pickup_qry.iter().find(|&(pickup_id, _)| {
+                rapier_ctx.intersection_pair(player_id, pickup_id) == Some(true)
+            })
+        {
+            player_thrower.held = Some(kind);
+            cmds.entity(pickup_id).despawn_recursive();
+        }
+    }
+}
+
+fn update_projectiles(
+    time: Res<Time>,
+    mut projectile_qry: Query<(Entity, &mut Projectile, &Transform)>,
+    hp_qry: Query<(), With<Health>>,
+    rapier_ctx: Res<RapierContext>,
+    mut cmds: Commands,
+    tile_assets: Res<TextureAtlasOwner<Tile>>,
+) {
+    for (projectile_id, mut projectile, projectile_xform) in &mut projectile_qry {
+        let has_expired = projectile.lifetime.tick(time.delta()).finished();
+        let has_hit_something = rapier_ctx
+            .contact_pairs_with(projectile_id)
+            .any(|contact_pair| contact_pair.has_any_active_contact())
+            || rapier_ctx
+                .intersection_pairs_with(projectile.hitbox)
+                .filter(|&(_, _, is_intersecting)| is_intersecting)
+                .map(|(a, b, _)| if a == projectile.hitbox { b } else { a })
+                .any(|target_id| Some(target_id) != projectile.owner && hp_qry.contains(target_id));
+
+        if !has_expired && !(projectile.kind.breaks_on_impact() && has_hit_something) {
+            continue;
+        }
+        cmds.entity(projectile_id).despawn_recursive();
+
+        if projectile.kind == ProjectileKind::Bomb {
+            // Explosions hurt everyone, the thrower included.
+            cmds.spawn((
+                Explosion {
+                    timer: Timer::new(EXPLOSION_DURATION, TimerMode::Once),
+                },
+                StateScoped(GameState::Playing),
+                SpriteBundle {
+                    transform: Transform::from_translation(
+                        projectile_xform.translation.truncate().extend(PROJECTILE_Z),
+                    )
+                    .with_scale(Vec3::splat(2. * EXPLOSION_RADIUS / TILE_SIZE.x)),
+                    texture: tile_assets.texture(),
+                    ..default()
+                },
+                TextureAtlas {
+                    layout: tile_assets.layout(),
+                    index: 25,
+                },
+                Collider::ball(TILE_SIZE.x / 2.),
+                Sensor,
+                Damage::fixed(2, 10.),
+                Name::new("Bomb"),
+            ));
+        }
+    }
+}
+
+fn update_explosions(
+    time: Res<Time>,
+    mut explosion_qry: Query<(Entity, &mut Explosion)>,
+    mut cmds: Commands,
+) {
+    for (explosion_id, mut explosion) in &mut explosion_qry {
+        if explosion.timer.tick(time.delta()).finished() {
+            cmds.entity(explosion_id).despawn_recursive();
+        }
+    }
+}
+
+/// Aims live players with a mouse at the cursor, and everyone else where they face.
+/// Replays set the aim of players with a mouse themselves.
+pub fn update_throw_aim(
+    mouse_pos: Res<MousePosition>,
+    mut player_qry: Query<
+        (
+            &Transform,
+            &Flippable,
+            &InputDevices,
+            &mut ThrowAim,
+            Has<InputMap<PlayerAction>>,
+        ),
+        With<Player>,
+    >,
+) {
+    for (player_xform, player_flippable, player_devices, mut player_aim, player_is_live) in
+        &mut player_qry
+    {
+        if !player_devices.has_mouse() {
+            player_aim.0 =
+                FACING_THROW_AIM * Vec2::new(if player_flippable.flip_x { -1. } else { 1. }, 1.);
+        } else if player_is_live {
+            player_aim.0 = mouse_pos.as_vec() - player_xform.translation.truncate();
+        }
+    }
+}
+
+pub fn throw_projectiles(
+    time: Res<Time>,
+    mut player_qry: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &Transform,
+            &ThrowAim,
+            &mut Thrower,
+        ),
+        (With<Player>, Without<DoorTransit>, Without<Dying>),
+    >,
+    mut projectile_spawn_evw: EventWriter<ProjectileSpawnEvent>,
+) {
+    for (player_id, player_in, player_xform, player_aim, mut player_thrower) in &mut player_qry {
+        player_thrower.cooldown.tick(time.delta());
+        if !player_thrower.cooldown.finished() || !player_in.just_pressed(&PlayerAction::Throw) {
+            continue;
+        }
+        player_thrower.cooldown = Timer::new(THROW_COOLDOWN, TimerMode::Once);
+
+        // Gravity bends the throw into an arc on its way to the cursor.
+        let dir = player_aim.0.try_normalize().unwrap_or(Vec2::X);
+        projectile_spawn_evw.send(ProjectileSpawnEvent {
+            pos: player_xform.translation.truncate() + dir * TILE_SIZE.x / 4.,
+            vel: dir * THROW_SPEED,
+            kind: player_thrower.held.take().unwrap_or(ProjectileKind::Rock),
+            owner: Some(player_id),
+        });
+    }
+}
+
+pub fn projectile_plugin(app: &mut App) {
+    app.add_event::<ProjectileSpawnEvent>()
+        .add_event::<ThrowableSpawnEvent>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            on_throwable_spawn.after(level::signal_level_object_spawns),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                (update_throw_aim, throw_projectiles, on_projectile_spawn)
+                    .chain()
+                    .before(PhysicsSet::SyncBackend),
+                (update_projectiles, update_explosions)
+                    .chain()
+                    .after(combat::deal_damage),
+                pick_up_throwables.after(PhysicsSet::Writeback),
+            )
+                .run_if(in_state(GameState::Playing)),
+        );
+}
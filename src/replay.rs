@@ -0,0 +1,330 @@
+use {
+    super::{
+        character::{Character, SelectedCharacter},
+        controls::InputDevices,
+        level::{LevelInfo, RunSeed, GENERATOR_VERSION},
+        movement::{MovementProfile, MovementProfileOwner},
+        player::{self, Player, PlayerAction, PlayerCount, MAX_PLAYERS},
+        projectile::{self, ThrowAim},
+        run_state::RunState,
+    },
+    crate::GameState,
+    bevy::{app::AppExit, prelude::*},
+    bevy_rapier2d::prelude::*,
+    bevy_tnua::prelude::*,
+    leafwing_input_manager::prelude::*,
+    serde::{Deserialize, Serialize},
+    static_assertions::const_assert,
+    std::{env, fs},
+};
+
+const REPLAY_PATH: &str = "replay.ron";
+const REPLAY_ARG: &str = "--replay";
+const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const_assert!(PlayerAction::ALL.len() * MAX_PLAYERS <= InputBits::BITS as usize);
+
+/// The pressed actions of one fixed tick, one bit per entry of [`PlayerAction::ALL`],
+/// with each player's bits above the previous player's.
+type InputBits = u32;
+
+/// A recorded run: everything needed to reproduce it tick for tick.
+#[derive(Serialize, Deserialize, Default)]
+struct Replay {
+    version: String,
+    /// The [`GENERATOR_VERSION`] the levels were recorded on, `0` from before it existed.
+    #[serde(default)]
+    generator_version: u32,
+    seed: u64,
+    #[serde(default)]
+    character: Character,
+    #[serde(default)]
+    player_count: PlayerCount,
+    /// What the run started the first recorded level with, which need not be the first level
+    /// when the run was continued from a save.
+    #[serde(default)]
+    run_state: Option<RunState>,
+    /// Run-length encoded `(input, tick count)` pairs, one list per level played.
+    levels: Vec<Vec<(InputBits, u32)>>,
+    /// `(tick, aim)` for every throw of the player with the mouse, one list per level played.
+    #[serde(default)]
+    aims: Vec<Vec<(u32, Vec2)>>,
+    /// The movement profile every level was played with, or `None` if it changed mid-level.
+    /// Playback is refused on a mismatch, since the same input would move players differently.
+    #[serde(default)]
+    profile_hashes: Vec<Option<u64>>,
+}
+
+impl Replay {
+    fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        ron::from_str(&contents).map_err(|err| err.to_string())
+    }
+
+    fn save(&self) {
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(REPLAY_PATH, contents).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            error!("failed to save {REPLAY_PATH}: {err}");
+        }
+    }
+
+    fn record(&mut self, input: InputBits, aim: Option<Vec2>) {
+        let (Some(level), Some(level_aims)) = (self.levels.last_mut(), self.aims.last_mut()) else {
+            return;
+        };
+        if let Some(aim) = aim {
+            level_aims.push((level.iter().map(|&(_, ticks)| ticks).sum(), aim));
+        }
+        match level.last_mut() {
+            Some((last_input, ticks)) if *last_input == input => *ticks += 1,
+            _ => level.push((input, 1)),
+        }
+    }
+}
+
+#[derive(Resource)]
+enum ReplayMode {
+    Recording(Replay),
+    Playback {
+        replay: Replay,
+        /// Index of the current level, `None` until the first level starts.
+        level: Option<usize>,
+        /// Inputs of the current level, decoded from its runs.
+        inputs: Vec<InputBits>,
+        aims: Vec<(u32, Vec2)>,
+        tick: usize,
+    },
+}
+
+fn input_bits(player_idx: usize, player_in: &ActionState<PlayerAction>) -> InputBits {
+    PlayerAction::ALL
+        .iter()
+        .enumerate()
+        .filter(|(_, action)| player_in.pressed(action))
+        .fold(0, |input, (bit, _)| {
+            input | 1 << (player_idx * PlayerAction::ALL.len() + bit)
+        })
+}
+
+fn start_replay(mut cmds: Commands, mut run_seed: ResMut<RunSeed>) {
+    let mut args = env::args().skip_while(|arg| arg != REPLAY_ARG).skip(1);
+    let Some(path) = args.next() else {
+        cmds.insert_resource(ReplayMode::Recording(Replay::default()));
+        return;
+    };
+
+    let replay = Replay::load(&path).and_then(|replay| {
+        if replay.generator_version == GENERATOR_VERSION {
+            Ok(replay)
+        } else {
+            Err(format!(
+                "recorded on levels of generator version {}, expected {GENERATOR_VERSION}",
+                replay.generator_version
+            ))
+        }
+    });
+    match replay {
+        Ok(replay) => {
+            if replay.version != GAME_VERSION {
+                warn!(
+                    "{path} was recorded with version {}, playback may diverge",
+                    replay.version
+                );
+            }
+            *run_seed = RunSeed(replay.seed);
+            cmds.insert_resource(ReplayMode::Playback {
+                replay,
+                level: None,
+                inputs: Vec::new(),
+                aims: Vec::new(),
+                tick: 0,
+            });
+        }
+        Err(err) => {
+            error!("failed to load {path}: {err}");
+            cmds.insert_resource(ReplayMode::Recording(Replay::default()));
+        }
+    }
+}
+
+/// Replays play as whoever was picked when they were recorded.
+fn skip_character_select(
+    replay_mode: Res<ReplayMode>,
+    mut selected_character: ResMut<SelectedCharacter>,
+    mut player_count: ResMut<PlayerCount>,
+    mut level_info: ResMut<LevelInfo>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut cmds: Commands,
+) {
+    if let ReplayMode::Playback { replay, .. } = replay_mode.as_ref() {
+        selected_character.0 = replay.character;
+        *player_count = replay.player_count;
+        if let Some(run_state) = &replay.run_state {
+            *level_info = run_state.level.clone();
+            cmds.insert_resource(run_state.clone());
+        }
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn start_replay_level(
+    mut replay_mode: ResMut<ReplayMode>,
+    run_seed: Res<RunSeed>,
+    selected_character: Res<SelectedCharacter>,
+    player_count: Res<PlayerCount>,
+    run_state: Option<Res<RunState>>,
+    player_qry: Query<Entity, With<Player>>,
+    profile_owner: Res<MovementProfileOwner>,
+    profiles: Res<Assets<MovementProfile>>,
+    mut app_exit_evw: EventWriter<AppExit>,
+    mut cmds: Commands,
+) {
+    let profile_hash = profile_owner.profile_hash(&profiles);
+
+    match replay_mode.as_mut() {
+        ReplayMode::Recording(replay) => {
+            if replay.levels.is_empty() {
+                *replay = Replay {
+                    version: String::from(GAME_VERSION),
+                    generator_version: GENERATOR_VERSION,
+                    seed: run_seed.0,
+                    character: selected_character.0,
+                    player_count: *player_count,
+                    run_state: run_state.as_deref().cloned(),
+                    ..default()
+                };
+            }
+            replay.levels.push(Vec::new());
+            replay.aims.push(Vec::new());
+            replay.profile_hashes.push(Some(profile_hash));
+        }
+        ReplayMode::Playback {
+            replay,
+            level,
+            inputs,
+            aims,
+            tick,
+        } => {
+            let next_level = level.map_or(0, |level| level + 1);
+            if replay.profile_hashes.get(next_level) != Some(&Some(profile_hash)) {
+                error!(
+                    "level {} of the replay was recorded with a different movement profile",
+                    next_level + 1
+                );
+                app_exit_evw.send(AppExit::error());
+                return;
+            }
+            *inputs = replay
+                .levels
+                .get(next_level)
+                .into_iter()
+                .flatten()
+                .flat_map(|&(input, ticks)| (0..ticks).map(move |_| input))
+                .collect();
+            *aims = replay.aims.get(next_level).cloned().unwrap_or_default();
+            *level = Some(next_level);
+            *tick = 0;
+
+            // Live input would otherwise overwrite the replayed actions.
+            for player_id in &player_qry {
+                cmds.entity(player_id).remove::<InputMap<PlayerAction>>();
+            }
+        }
+    }
+}
+
+fn drive_replay_input(
+    mut replay_mode: ResMut<ReplayMode>,
+    profile_owner: Res<MovementProfileOwner>,
+    profiles: Res<Assets<MovementProfile>>,
+    mut player_qry: Query<(
+        &Player,
+        &mut ActionState<PlayerAction>,
+        &mut ThrowAim,
+        &InputDevices,
+    )>,
+) {
+    match replay_mode.as_mut() {
+        ReplayMode::Recording(replay) => {
+            let input = player_qry
+                .iter()
+                .fold(0, |input, (&Player(idx), player_in, _, _)| {
+                    input | input_bits(idx, player_in)
+                });
+            let aim = player_qry
+                .iter()
+                .find(|(_, player_in, _, devices)| {
+                    devices.has_mouse() && player_in.just_pressed(&PlayerAction::Throw)
+                })
+                .map(|(_, _, player_aim, _)| player_aim.0);
+            replay.record(input, aim);
+
+            // Hot reloads mid-level cannot be reproduced.
+            let profile_hash = profile_owner.profile_hash(&profiles);
+            if let Some(level_profile_hash) = replay.profile_hashes.last_mut() {
+                if *level_profile_hash != Some(profile_hash) {
+                    *level_profile_hash = None;
+                }
+            }
+        }
+        ReplayMode::Playback {
+            inputs, aims, tick, ..
+        } => {
+            let input = inputs.get(*tick).copied().unwrap_or_default();
+            let aim = aims
+                .iter()
+                .find(|&&(aim_tick, _)| aim_tick as usize == *tick)
+                .map(|&(_, aim)| aim);
+            *tick += 1;
+
+            for (&Player(idx), mut player_in, mut player_aim, devices) in &mut player_qry {
+                if let (Some(aim), true) = (aim, devices.has_mouse()) {
+                    player_aim.0 = aim;
+                }
+                for (bit, action) in PlayerAction::ALL.iter().enumerate() {
+                    if input & 1 << (idx * PlayerAction::ALL.len() + bit) != 0 {
+                        player_in.press(action);
+                    } else {
+                        player_in.release(action);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn replay_plugin(app: &mut App) {
+    app.add_systems(Startup, start_replay)
+        .add_systems(OnEnter(GameState::CharacterSelect), skip_character_select)
+        .add_systems(
+            OnEnter(GameState::Playing),
+            // Runs once the player has been spawned so its live input can be removed.
+            start_replay_level.after(player::on_player_spawn),
+        )
+        .add_systems(
+            OnExit(GameState::Playing),
+            |replay_mode: Res<ReplayMode>| {
+                if let ReplayMode::Recording(replay) = replay_mode.as_ref() {
+                    replay.save();
+                }
+            },
+        )
+        .add_systems(
+            OnExit(GameState::GameOver),
+            |mut replay_mode: ResMut<ReplayMode>| {
+                *replay_mode = ReplayMode::Recording(Replay::default());
+            },
+        )
+        .add_systems(
+            FixedUpdate,
+            drive_replay_input
+                .after(projectile::update_throw_aim)
+                .before(projectile::throw_projectiles)
+                .before(TnuaUserControlsSystemSet)
+                .before(PhysicsSet::SyncBackend)
+                .run_if(in_state(GameState::Playing)),
+        );
+}
@@ -0,0 +1,171 @@
+use {
+    super::{
+        character::Character,
+        combat::{Health, MaxHealth},
+        item::Inventory,
+        level::{self, LevelInfo},
+        stats::RunStats,
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    serde::{Deserialize, Serialize},
+    std::{fs, io},
+};
+
+const RUN_STATE_PATH: &str = "run.ron";
+/// Bumped whenever [`RunState`] changes in a way older saves cannot be read as.
+const RUN_STATE_VERSION: u32 = 3;
+const LEVEL_GOLD: u32 = 10;
+const BONUS_LEVEL_GOLD: u32 = 30;
+
+/// Read before the rest of a save, to reject saves of another version up front.
+#[derive(Deserialize)]
+#[serde(rename = "RunState")]
+struct RunStateHeader {
+    version: u32,
+}
+
+/// Chosen before a run starts to make it harder or more rewarding.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RunModifier {
+    /// Everyone starts with half their hearts, rounded down to whole hearts.
+    Fragile,
+    /// Cleared levels pay out double gold.
+    Wealthy,
+}
+
+impl RunModifier {
+    pub const ALL: [Self; 2] = [Self::Fragile, Self::Wealthy];
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerRunState {
+    pub character: Character,
+    pub max_health: MaxHealth,
+    /// Zero for a player who was downed, and who rejoins at the next level.
+    pub hp: Health,
+    pub inventory: Inventory,
+}
+
+impl PlayerRunState {
+    fn new(character: Character, modifiers: &[RunModifier]) -> Self {
+        let mut max_health = MaxHealth(character.stats().max_health.0);
+        if modifiers.contains(&RunModifier::Fragile) {
+            max_health.0 = (max_health.0 / 4).max(1) * 2;
+        }
+        Self {
+            character,
+            max_health,
+            hp: Health(max_health.0),
+            inventory: Inventory::default(),
+        }
+    }
+}
+
+/// Everything that carries over from one level to the next, created when the run starts,
+/// updated at the exit door and saved to [`RUN_STATE_PATH`] between levels.
+#[derive(Resource, Serialize, Deserialize, Clone)]
+pub struct RunState {
+    version: u32,
+    pub seed: u64,
+    /// The level being played, or the one to continue from once saved.
+    pub level: LevelInfo,
+    /// Indexed like [`Player`](super::player::Player).
+    pub players: Vec<PlayerRunState>,
+    /// Shared by all players.
+    pub gold: u32,
+    pub modifiers: Vec<RunModifier>,
+    pub stats: RunStats,
+}
+
+impl RunState {
+    pub fn new(
+        seed: u64,
+        characters: impl IntoIterator<Item = Character>,
+        modifiers: Vec<RunModifier>,
+    ) -> Self {
+        Self {
+            version: RUN_STATE_VERSION,
+            seed,
+            level: LevelInfo::DEFAULT,
+            players: characters
+                .into_iter()
+                .map(|character| PlayerRunState::new(character, &modifiers))
+                .collect(),
+            gold: 0,
+            modifiers,
+            stats: RunStats::default(),
+        }
+    }
+
+    /// Pays out for clearing the level described by `level_info`.
+    pub fn award_level_gold(&mut self, level_info: &LevelInfo) {
+        let gold = if level_info.is_bonus() {
+            BONUS_LEVEL_GOLD
+        } else {
+            LEVEL_GOLD
+        };
+        self.gold += if self.modifiers.contains(&RunModifier::Wealthy) {
+            2 * gold
+        } else {
+            gold
+        };
+    }
+
+    /// The run saved between levels, if there is one that this version can continue.
+    pub fn load() -> Option<Self> {
+        let contents = fs::read_to_string(RUN_STATE_PATH).ok()?;
+        let result = ron::from_str::<RunStateHeader>(&contents)
+            .map_err(|err| err.to_string())
+            .and_then(|header| {
+                if header.version == RUN_STATE_VERSION {
+                    ron::from_str(&contents).map_err(|err| err.to_string())
+                } else {
+                    Err(format!(
+                        "saved with version {}, expected {RUN_STATE_VERSION}",
+                        header.version
+                    ))
+                }
+            });
+
+        result
+            .map_err(|err| warn!("ignoring invalid {RUN_STATE_PATH}: {err}"))
+            .ok()
+    }
+
+    pub fn save(&self) {
+        let result = ron::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                fs::write(RUN_STATE_PATH, contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            error!("failed to save {RUN_STATE_PATH}: {err}");
+        }
+    }
+}
+
+pub fn run_state_plugin(app: &mut App) {
+    app.add_systems(
+        OnEnter(GameState::Transition),
+        (|level_info: Res<LevelInfo>, run_state: Option<ResMut<RunState>>| {
+            if let Some(mut run_state) = run_state {
+                run_state.level = level_info.clone();
+                run_state.save();
+            }
+        })
+        .after(level::advance_level),
+    )
+    // A finished run cannot be continued.
+    .add_systems(OnEnter(GameState::GameOver), || {
+        if let Err(err) = fs::remove_file(RUN_STATE_PATH) {
+            if err.kind() != io::ErrorKind::NotFound {
+                error!("failed to delete {RUN_STATE_PATH}: {err}");
+            }
+        }
+    })
+    .add_systems(OnExit(GameState::GameOver), |mut cmds: Commands| {
+        cmds.remove_resource::<RunState>();
+    });
+}
@@ -1,8 +1,9 @@
 use {
     super::{
         asset_owner::TextureAtlasOwner,
-        combat::Damage,
+        combat::{Damage, Hazard},
         level,
+        player::Player,
         tile::{Tile, TILE_SIZE, TILE_Z},
     },
     crate::GameState,
@@ -16,6 +17,12 @@ const SPIKE_COLLIDER_SIZE: Vec2 = Vec2::new(TILE_SIZE.x * 2. / 3., TILE_SIZE.y /
 #[derive(Component)]
 pub struct Spike;
 
+/// Sent when a player starts touching spikes, whether or not they get hurt.
+#[derive(Event)]
+pub struct SpikeTouchEvent {
+    pub player: Entity,
+}
+
 #[derive(Event)]
 pub struct SpikeSpawnEvent {
     pub pos: Vec2,
@@ -49,7 +56,10 @@ fn on_spike_spawn(
             parent.spawn((
                 Collider::cuboid(SPIKE_COLLIDER_SIZE.x / 2., SPIKE_COLLIDER_SIZE.y / 2.),
                 Sensor,
-                Damage::Fixed(1),
+                ActiveEvents::COLLISION_EVENTS,
+                Damage::fixed(1, 4.),
+                Hazard,
+                Name::new("Spikes"),
                 SpatialBundle::from_transform(Transform::from_xyz(
                     0.,
                     {
@@ -67,9 +77,39 @@ fn on_spike_spawn(
     }
 }
 
+fn signal_spike_touches(
+    mut collision_evr: EventReader<CollisionEvent>,
+    sensor_qry: Query<&Parent, With<Sensor>>,
+    spike_qry: Query<(), With<Spike>>,
+    player_qry: Query<(), With<Player>>,
+    mut spike_touch_evw: EventWriter<SpikeTouchEvent>,
+) {
+    for collision in collision_evr.read() {
+        let &CollisionEvent::Started(a, b, _) = collision else {
+            continue;
+        };
+        for (player_id, spike_id) in [(a, b), (b, a)] {
+            let is_spike = sensor_qry
+                .get(spike_id)
+                .is_ok_and(|sensor_parent| spike_qry.contains(sensor_parent.get()));
+            if player_qry.contains(player_id) && is_spike {
+                spike_touch_evw.send(SpikeTouchEvent { player: player_id });
+            }
+        }
+    }
+}
+
 pub fn spike_plugin(app: &mut App) {
-    app.add_event::<SpikeSpawnEvent>().add_systems(
-        OnEnter(GameState::Playing),
-        on_spike_spawn.after(level::signal_level_object_spawns),
-    );
+    app.add_event::<SpikeSpawnEvent>()
+        .add_event::<SpikeTouchEvent>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            on_spike_spawn.after(level::signal_level_object_spawns),
+        )
+        .add_systems(
+            FixedUpdate,
+            signal_spike_touches
+                .after(PhysicsSet::Writeback)
+                .run_if(in_state(GameState::Playing)),
+        );
 }
@@ -0,0 +1,226 @@
+use {
+    super::{
+        combat::DamageEvent,
+        door::DoorEnterEvent,
+        level::LevelInfo,
+        player::{Player, PlayerJumpEvent},
+        run_state::RunState,
+        spike::SpikeTouchEvent,
+        tile::TILE_SIZE,
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    bevy_rapier2d::prelude::*,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, fs},
+};
+
+const LIFETIME_STATS_PATH: &str = "lifetime_stats.ron";
+const UNKNOWN_DAMAGE_SOURCE: &str = "Unknown";
+
+/// What happened during a run, summed over every player.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStats {
+    pub jumps: u32,
+    pub air_jumps: u32,
+    pub damage_taken: BTreeMap<String, u32>,
+    pub spikes_touched: u32,
+    /// Seconds spent in each level played, in order.
+    pub level_times: Vec<(String, f32)>,
+    /// In tiles.
+    pub distance: f32,
+    /// One per player going through a door.
+    pub doors_entered: u32,
+    /// Counted once everyone is through the exit, however many doors that took.
+    pub levels_cleared: u32,
+}
+
+impl RunStats {
+    pub fn summary(&self) -> String {
+        let damage_taken = if self.damage_taken.is_empty() {
+            String::from("none")
+        } else {
+            self.damage_taken
+                .iter()
+                .map(|(source, amount)| format!("{source} {amount}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let level_times = self
+            .level_times
+            .iter()
+            .map(|(level, secs)| format!("{level} {secs:.1}s"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "Jumps {} ({} in the air)    Distance {:.0} tiles    Doors {}    Levels cleared {}    \
+             Spikes touched {}\n\
+             Damage taken: {damage_taken}\n\
+             Level times: {level_times}",
+            self.jumps,
+            self.air_jumps,
+            self.distance,
+            self.doors_entered,
+            self.levels_cleared,
+            self.spikes_touched,
+        )
+    }
+}
+
+/// Totals over every run ever finished, persisted to [`LIFETIME_STATS_PATH`].
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LifetimeStats {
+    runs: u32,
+    levels_played: u32,
+    /// In seconds.
+    time_played: f32,
+    jumps: u32,
+    air_jumps: u32,
+    damage_taken: u32,
+    spikes_touched: u32,
+    distance: f32,
+    doors_entered: u32,
+    levels_cleared: u32,
+}
+
+impl LifetimeStats {
+    fn load() -> Self {
+        match fs::read_to_string(LIFETIME_STATS_PATH) {
+            Ok(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+                warn!("ignoring invalid {LIFETIME_STATS_PATH}: {err}");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                fs::write(LIFETIME_STATS_PATH, contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            error!("failed to save {LIFETIME_STATS_PATH}: {err}");
+        }
+    }
+
+    fn add(&mut self, run_stats: &RunStats) {
+        self.runs += 1;
+        self.levels_played += run_stats.level_times.len() as u32;
+        self.time_played += run_stats
+            .level_times
+            .iter()
+            .map(|&(_, secs)| secs)
+            .sum::<f32>();
+        self.jumps += run_stats.jumps;
+        self.air_jumps += run_stats.air_jumps;
+        self.damage_taken += run_stats.damage_taken.values().sum::<u32>();
+        self.spikes_touched += run_stats.spikes_touched;
+        self.distance += run_stats.distance;
+        self.doors_entered += run_stats.doors_entered;
+        self.levels_cleared += run_stats.levels_cleared;
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Lifetime: {} runs    {} levels    {:.0} minutes    {} jumps    {:.0} tiles    {} damage taken",
+            self.runs,
+            self.levels_played,
+            self.time_played / 60.,
+            self.jumps,
+            self.distance,
+            self.damage_taken,
+        )
+    }
+}
+
+/// Seconds spent in the current level.
+#[derive(Resource, Default)]
+struct LevelTime(f32);
+
+fn collect_event_stats(
+    mut jump_evr: EventReader<PlayerJumpEvent>,
+    mut dmg_evr: EventReader<DamageEvent>,
+    mut spike_touch_evr: EventReader<SpikeTouchEvent>,
+    mut door_enter_evr: EventReader<DoorEnterEvent>,
+    player_qry: Query<(), With<Player>>,
+    mut run_state: ResMut<RunState>,
+) {
+    let stats = &mut run_state.stats;
+
+    for jump in jump_evr.read() {
+        stats.jumps += 1;
+        stats.air_jumps += jump.is_air_jump as u32;
+    }
+    for dmg in dmg_evr.read() {
+        if player_qry.contains(dmg.target) {
+            *stats
+                .damage_taken
+                .entry(
+                    dmg.source_name
+                        .clone()
+                        .unwrap_or_else(|| String::from(UNKNOWN_DAMAGE_SOURCE)),
+                )
+                .or_default() += dmg.amount as u32;
+        }
+    }
+    stats.spikes_touched += spike_touch_evr.read().count() as u32;
+    stats.doors_entered += door_enter_evr.read().count() as u32;
+}
+
+fn measure_distance(
+    time: Res<Time>,
+    player_qry: Query<&Velocity, With<Player>>,
+    mut run_state: ResMut<RunState>,
+    mut level_time: ResMut<LevelTime>,
+) {
+    level_time.0 += time.delta_seconds();
+    for player_velocity in &player_qry {
+        run_state.stats.distance +=
+            player_velocity.linvel.length() * time.delta_seconds() / TILE_SIZE.x;
+    }
+}
+
+fn record_level_time(
+    level_info: Res<LevelInfo>,
+    level_time: Res<LevelTime>,
+    run_state: Option<ResMut<RunState>>,
+) {
+    if let Some(mut run_state) = run_state {
+        run_state
+            .stats
+            .level_times
+            .push((level_info.to_string(), level_time.0));
+    }
+}
+
+pub fn record_lifetime_stats(
+    mut lifetime_stats: ResMut<LifetimeStats>,
+    run_state: Option<Res<RunState>>,
+) {
+    if let Some(run_state) = run_state {
+        lifetime_stats.add(&run_state.stats);
+        lifetime_stats.save();
+    }
+}
+
+pub fn stats_plugin(app: &mut App) {
+    app.insert_resource(LifetimeStats::load())
+        .init_resource::<LevelTime>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            |mut level_time: ResMut<LevelTime>| level_time.0 = 0.,
+        )
+        .add_systems(OnExit(GameState::Playing), record_level_time)
+        .add_systems(OnEnter(GameState::GameOver), record_lifetime_stats)
+        .add_systems(
+            FixedUpdate,
+            (collect_event_stats, measure_distance)
+                .after(PhysicsSet::Writeback)
+                .run_if(in_state(GameState::Playing).and_then(resource_exists::<RunState>)),
+        );
+}
@@ -0,0 +1,83 @@
+use {
+    super::{
+        controls::{Controls, InputDevices},
+        player::PlayerAction,
+    },
+    bevy::{
+        input::{
+            gamepad::{
+                GamepadAxisChangedEvent, GamepadButtonChangedEvent, GamepadConnection,
+                GamepadConnectionEvent, GamepadEvent, GamepadInfo,
+            },
+            InputPlugin,
+        },
+        prelude::*,
+    },
+    leafwing_input_manager::prelude::*,
+};
+
+pub fn gamepad() -> Gamepad {
+    Gamepad::new(0)
+}
+
+/// An app that reads input into [`ActionState`]s, with [`gamepad`] connected on the first
+/// update.
+pub fn input_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        InputPlugin,
+        InputManagerPlugin::<PlayerAction>::default(),
+    ))
+    .insert_resource(Controls::default());
+    app.world_mut()
+        .send_event(GamepadEvent::Connection(GamepadConnectionEvent::new(
+            gamepad(),
+            GamepadConnection::Connected(GamepadInfo {
+                name: String::from("Test gamepad"),
+            }),
+        )));
+    app
+}
+
+pub fn player_input(devices: InputDevices) -> InputManagerBundle<PlayerAction> {
+    InputManagerBundle::with_map(Controls::default().input_map(devices))
+}
+
+/// Presses or releases the default key of `action`, from the next update on.
+pub fn set_action(app: &mut App, action: PlayerAction, is_pressed: bool) {
+    let key = Controls::default().key(&action).unwrap();
+    let mut kb = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    if is_pressed {
+        kb.press(key);
+    } else {
+        kb.release(key);
+    }
+}
+
+/// Presses or releases `button` on [`gamepad`], from the next update on.
+pub fn set_button(app: &mut App, button: GamepadButtonType, is_pressed: bool) {
+    app.world_mut()
+        .send_event(GamepadEvent::Button(GamepadButtonChangedEvent::new(
+            gamepad(),
+            button,
+            if is_pressed { 1. } else { 0. },
+        )));
+}
+
+/// Moves a stick of [`gamepad`] to the raw `value`, from the next update on.
+pub fn set_axis(app: &mut App, axis: GamepadAxisType, value: f32) {
+    app.world_mut()
+        .send_event(GamepadEvent::Axis(GamepadAxisChangedEvent::new(
+            gamepad(),
+            axis,
+            value,
+        )));
+}
+
+pub fn is_pressed(app: &App, player_id: Entity, action: PlayerAction) -> bool {
+    app.world()
+        .get::<ActionState<PlayerAction>>(player_id)
+        .unwrap()
+        .pressed(&action)
+}
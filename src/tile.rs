@@ -1,5 +1,5 @@
 use {
-    super::{asset_owner::TextureAtlasOwner, level},
+    super::{asset_owner::TextureAtlasOwner, combat::Health, level},
     crate::GameState,
     bevy::prelude::*,
     bevy_rapier2d::prelude::*,
@@ -7,6 +7,7 @@ use {
 
 pub const TILE_Z: f32 = 1.;
 pub const TILE_SIZE: Vec2 = Vec2::splat(128.);
+const BREAKABLE_TILE_HEALTH: Health = Health(1);
 
 #[derive(Component)]
 pub struct Tile;
@@ -15,6 +16,7 @@ pub struct Tile;
 pub struct TileSpawnEvent {
     pub pos: Vec2,
     pub tex_idx: usize,
+    pub is_breakable: bool,
 }
 
 fn on_tile_spawn(
@@ -22,8 +24,13 @@ fn on_tile_spawn(
     mut cmds: Commands,
     tile_assets: Res<TextureAtlasOwner<Tile>>,
 ) {
-    for &TileSpawnEvent { pos, tex_idx } in tile_spawn_evr.read() {
-        cmds.spawn((
+    for &TileSpawnEvent {
+        pos,
+        tex_idx,
+        is_breakable,
+    } in tile_spawn_evr.read()
+    {
+        let mut tile_cmds = cmds.spawn((
             Tile,
             StateScoped(GameState::Playing),
             SpriteBundle {
@@ -37,6 +44,9 @@ fn on_tile_spawn(
             },
             Collider::cuboid(TILE_SIZE.x / 2., TILE_SIZE.y / 2.),
         ));
+        if is_breakable {
+            tile_cmds.insert(BREAKABLE_TILE_HEALTH);
+        }
     }
 }
 
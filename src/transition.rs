@@ -0,0 +1,146 @@
+use {
+    super::{
+        asset_owner::FontOwner,
+        door::DoorEnterEvent,
+        level::{self, LevelInfo},
+        player::DOOR_TRANSIT_DURATION,
+    },
+    crate::GameState,
+    bevy::prelude::*,
+    std::time::Duration,
+};
+
+const TITLE_CARD_DURATION: Duration = Duration::from_millis(1500);
+const FADE_Z_INDEX: ZIndex = ZIndex::Global(1);
+
+#[derive(Component)]
+struct TitleCard;
+
+#[derive(Resource)]
+struct TitleCardTimer(Timer);
+
+#[derive(Component)]
+struct Fade {
+    timer: Timer,
+    is_fading_in: bool,
+}
+
+fn spawn_fade(cmds: &mut Commands, is_fading_in: bool) {
+    cmds.spawn((
+        Fade {
+            timer: Timer::new(DOOR_TRANSIT_DURATION, TimerMode::Once),
+            is_fading_in,
+        },
+        StateScoped(GameState::Playing),
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            background_color: Color::BLACK
+                .with_alpha(if is_fading_in { 1. } else { 0. })
+                .into(),
+            z_index: FADE_Z_INDEX,
+            ..default()
+        },
+    ));
+}
+
+/// Everyone goes through the door together, so one fade covers the whole entry.
+fn fade_out_on_door_enter(mut door_enter_evr: EventReader<DoorEnterEvent>, mut cmds: Commands) {
+    if door_enter_evr.read().count() > 0 {
+        spawn_fade(&mut cmds, false);
+    }
+}
+
+fn update_fades(
+    time: Res<Time>,
+    mut fade_qry: Query<(Entity, &mut Fade, &mut BackgroundColor)>,
+    mut cmds: Commands,
+) {
+    for (fade_id, mut fade, mut fade_color) in &mut fade_qry {
+        fade.timer.tick(time.delta());
+        fade_color.0.set_alpha(if fade.is_fading_in {
+            fade.timer.fraction_remaining()
+        } else {
+            fade.timer.fraction()
+        });
+
+        if fade.is_fading_in && fade.timer.just_finished() {
+            cmds.entity(fade_id).despawn_recursive();
+        }
+    }
+}
+
+fn spawn_title_card(
+    mut cmds: Commands,
+    title_card_font: Res<FontOwner<TitleCard>>,
+    level_info: Res<LevelInfo>,
+) {
+    cmds.insert_resource(TitleCardTimer(Timer::new(
+        TITLE_CARD_DURATION,
+        TimerMode::Once,
+    )));
+    cmds.spawn((
+        TitleCard,
+        StateScoped(GameState::Transition),
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::BLACK.into(),
+            ..default()
+        },
+    ))
+    .with_children(|title_card| {
+        title_card.spawn(TextBundle::from_section(
+            format!("World {}", *level_info),
+            TextStyle {
+                font: title_card_font.font(),
+                font_size: 80.,
+                color: Color::WHITE,
+            },
+        ));
+    });
+}
+
+fn update_title_card(
+    time: Res<Time>,
+    mut title_card_timer: ResMut<TitleCardTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if title_card_timer.0.tick(time.delta()).just_finished() {
+        next_state.set(GameState::Playing);
+    }
+}
+
+pub fn transition_plugin(app: &mut App) {
+    app.add_systems(
+        OnEnter(GameState::Setup),
+        |mut cmds: Commands, asset_server: Res<AssetServer>| {
+            cmds.insert_resource(FontOwner::<TitleCard>::new(asset_server.load("font.ttf")));
+        },
+    )
+    .add_systems(OnEnter(GameState::Playing), |mut cmds: Commands| {
+        spawn_fade(&mut cmds, true)
+    })
+    .add_systems(
+        OnEnter(GameState::Transition),
+        spawn_title_card.after(level::advance_level),
+    )
+    .add_systems(
+        Update,
+        (
+            (fade_out_on_door_enter, update_fades)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+            update_title_card.run_if(in_state(GameState::Transition)),
+        ),
+    );
+}
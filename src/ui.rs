@@ -1,9 +1,12 @@
 use {
     super::{
         asset_owner::{FontOwner, TextureAtlasOwner},
-        combat::Health,
+        combat::{Health, MaxHealth},
+        ghost::LevelSplit,
+        item::Inventory,
         level::LevelInfo,
-        player::{self, Player, PLAYER_MAX_HEALTH},
+        player::{self, Player, PlayerCount},
+        run_state::RunState,
         tile::Tile,
     },
     crate::{GameState, RESOLUTION},
@@ -14,14 +17,22 @@ use {
 #[derive(Component)]
 struct Ui;
 
+/// Belongs to the [`Player`] with the same index, like [`InventoryDisplay`].
 #[derive(Component)]
-struct Healthbar;
+struct Healthbar(usize);
+
+#[derive(Component)]
+struct InventoryDisplay(usize);
+
+#[derive(Component)]
+struct SplitDisplay;
 
 fn spawn_hud(
     mut cmds: Commands,
-    tile_assets: Res<TextureAtlasOwner<Tile>>,
     ui_font: Res<FontOwner<Ui>>,
     level_info: Res<LevelInfo>,
+    player_count: Res<PlayerCount>,
+    run_state: Res<RunState>,
 ) {
     cmds.spawn((
         NodeBundle {
@@ -39,7 +50,7 @@ fn spawn_hud(
             .spawn(NodeBundle {
                 style: Style {
                     width: Val::Percent(100.),
-                    height: Val::Percent(10.),
+                    height: Val::Percent(10. * player_count.0 as f32),
                     justify_content: JustifyContent::SpaceBetween,
                     align_items: AlignItems::Center,
                     ..default()
@@ -47,46 +58,77 @@ fn spawn_hud(
                 ..default()
             })
             .with_children(|hud| {
-                hud.spawn((
-                    Healthbar,
-                    NodeBundle {
-                        style: Style {
-                            width: Val::Percent(30.),
-                            height: Val::Percent(100.),
-                            justify_content: JustifyContent::SpaceEvenly,
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
+                hud.spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(50.),
+                        height: Val::Percent(100.),
+                        flex_direction: FlexDirection::Column,
                         ..default()
                     },
-                ))
-                .with_children(|healthbar| {
-                    for _ in 0..PLAYER_MAX_HEALTH.0 / 2 {
-                        healthbar.spawn((
-                            ImageBundle {
-                                image: UiImage::new(tile_assets.texture()),
+                    ..default()
+                })
+                .with_children(|player_rows| {
+                    for idx in 0..player_count.0 {
+                        player_rows
+                            .spawn(NodeBundle {
                                 style: Style {
-                                    max_width: Val::Percent(
-                                        100. / (PLAYER_MAX_HEALTH.0 / 2) as f32,
-                                    ),
-                                    max_height: Val::Percent(100.),
+                                    height: Val::Percent(100. / player_count.0 as f32),
+                                    align_items: AlignItems::Center,
                                     ..default()
                                 },
                                 ..default()
-                            },
-                            TextureAtlas {
-                                layout: tile_assets.layout(),
-                                index: 39,
-                            },
-                        ));
+                            })
+                            .with_children(|player_row| {
+                                player_row.spawn((
+                                    Healthbar(idx),
+                                    NodeBundle {
+                                        style: Style {
+                                            width: Val::Percent(60.),
+                                            height: Val::Percent(100.),
+                                            justify_content: JustifyContent::SpaceEvenly,
+                                            align_items: AlignItems::Center,
+                                            ..default()
+                                        },
+                                        ..default()
+                                    },
+                                ));
+                                player_row.spawn((
+                                    InventoryDisplay(idx),
+                                    NodeBundle {
+                                        style: Style {
+                                            height: Val::Percent(60.),
+                                            align_items: AlignItems::Center,
+                                            column_gap: Val::Px(4.),
+                                            ..default()
+                                        },
+                                        ..default()
+                                    },
+                                ));
+                            });
                     }
                 });
-                hud.spawn(TextBundle::from_section(
-                    format!(
-                        "{world}-{level}",
-                        world = level_info.world(),
-                        level = level_info.level()
+                hud.spawn((
+                    SplitDisplay,
+                    TextBundle::from_section(
+                        String::new(),
+                        TextStyle {
+                            font: ui_font.font(),
+                            font_size: 40.,
+                            ..default()
+                        },
                     ),
+                ));
+                hud.spawn(TextBundle::from_section(
+                    format!("{} gold", run_state.gold),
+                    TextStyle {
+                        font: ui_font.font(),
+                        font_size: 40.,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ));
+                hud.spawn(TextBundle::from_section(
+                    level_info.to_string(),
                     TextStyle {
                         font: ui_font.font(),
                         font_size: 40.,
@@ -98,26 +140,117 @@ fn spawn_hud(
     });
 }
 
+/// Rebuilds a player's hearts and item icons whenever their upgrades change.
+fn rebuild_inventory_hud(
+    healthbar_qry: Query<(Entity, &Healthbar)>,
+    inventory_display_qry: Query<(Entity, &InventoryDisplay)>,
+    player_qry: Query<
+        (&Player, &Inventory, &MaxHealth),
+        Or<(Changed<Inventory>, Changed<MaxHealth>)>,
+    >,
+    tile_assets: Res<TextureAtlasOwner<Tile>>,
+    mut cmds: Commands,
+) {
+    for (&Player(idx), player_inventory, player_max_hp) in &player_qry {
+        let (Some((healthbar_id, _)), Some((inventory_display_id, _))) = (
+            healthbar_qry
+                .iter()
+                .find(|(_, healthbar)| healthbar.0 == idx),
+            inventory_display_qry
+                .iter()
+                .find(|(_, inventory_display)| inventory_display.0 == idx),
+        ) else {
+            continue;
+        };
+
+        let heart_count = player_max_hp.0 / 2;
+        cmds.entity(healthbar_id)
+            .despawn_descendants()
+            .with_children(|healthbar| {
+                for _ in 0..heart_count {
+                    healthbar.spawn((
+                        ImageBundle {
+                            image: UiImage::new(tile_assets.texture()),
+                            style: Style {
+                                max_width: Val::Percent(100. / heart_count as f32),
+                                max_height: Val::Percent(100.),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        TextureAtlas {
+                            layout: tile_assets.layout(),
+                            index: 39,
+                        },
+                    ));
+                }
+            });
+
+        cmds.entity(inventory_display_id)
+            .despawn_descendants()
+            .with_children(|inventory_display| {
+                for item in player_inventory.items() {
+                    inventory_display.spawn((
+                        ImageBundle {
+                            image: UiImage::new(tile_assets.texture()),
+                            style: Style {
+                                max_height: Val::Percent(100.),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        TextureAtlas {
+                            layout: tile_assets.layout(),
+                            index: item.tex_idx(),
+                        },
+                    ));
+                }
+            });
+    }
+}
+
 fn update_hud(
-    healthbar_qry: Query<&Children, With<Healthbar>>,
+    healthbar_qry: Query<(&Healthbar, &Children)>,
     mut tex_atlas_qry: Query<&mut TextureAtlas>,
-    player_qry: Query<&Health, With<Player>>,
+    player_qry: Query<(&Player, &Health)>,
 ) {
-    let mut player_hp = player_qry.single().0;
-
-    for &heart_id in healthbar_qry.single().iter() {
-        let Ok(mut heart_tex_atlas) = tex_atlas_qry.get_mut(heart_id) else {
+    for (&Player(idx), &Health(mut player_hp)) in &player_qry {
+        let Some((_, hearts)) = healthbar_qry
+            .iter()
+            .find(|(healthbar, _)| healthbar.0 == idx)
+        else {
             continue;
         };
-        heart_tex_atlas.index = match player_hp.cmp(&1) {
-            Ordering::Less => 39,
-            Ordering::Equal => 53,
-            Ordering::Greater => 67,
-        };
-        player_hp -= 2;
+
+        for &heart_id in hearts {
+            let Ok(mut heart_tex_atlas) = tex_atlas_qry.get_mut(heart_id) else {
+                continue;
+            };
+            heart_tex_atlas.index = match player_hp.cmp(&1) {
+                Ordering::Less => 39,
+                Ordering::Equal => 53,
+                Ordering::Greater => 67,
+            };
+            player_hp -= 2;
+        }
     }
 }
 
+fn show_split(
+    level_split: Res<LevelSplit>,
+    mut split_display_qry: Query<&mut Text, With<SplitDisplay>>,
+) {
+    let Ok(mut split_text) = split_display_qry.get_single_mut() else {
+        return;
+    };
+    split_text.sections[0].value = format!("{:+.2}s", level_split.0);
+    split_text.sections[0].style.color = if level_split.0 < 0. {
+        Color::srgb(0., 0.6, 0.)
+    } else {
+        Color::srgb(0.8, 0., 0.)
+    };
+}
+
 pub fn ui_plugin(app: &mut App) {
     app.add_systems(
         OnEnter(GameState::Setup),
@@ -129,5 +262,12 @@ pub fn ui_plugin(app: &mut App) {
         OnEnter(GameState::Playing),
         spawn_hud.after(player::on_player_spawn),
     )
-    .add_systems(Update, update_hud.run_if(in_state(GameState::Playing)));
+    .add_systems(
+        Update,
+        (
+            (rebuild_inventory_hud, update_hud).chain(),
+            show_split.run_if(resource_added::<LevelSplit>),
+        )
+            .run_if(in_state(GameState::Playing)),
+    );
 }